@@ -5,14 +5,103 @@ use socketcan::{CanFilter, Frame};  use core::f32;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::time::Duration;
 use socketcan::{CanSocket, EmbeddedFrame, Socket};
 use canparse::pgn::{ParseMessage, PgnLibrary};
-use socketcan::CanFrame;
+use socketcan::{CanFrame, ExtendedId};
+
+// Bit layout of one DBC signal, used to encode the frames we send
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignalLayout {
+    pub name: String,
+    // DBC start bit: LSB for little endian (Intel), MSB for big endian (Motorola) signals
+    pub start_bit: usize,
+    pub bit_len: usize,
+    pub little_endian: bool,
+    pub signed: bool,
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl SignalLayout {
+    // " SG_ name : 0|8@1+ (1,0) [0|255] \"unit\" receivers", multiplexer markers included
+    fn parse(line: &str) -> Option<Self> {
+        let (head, tail) = line.trim().strip_prefix("SG_ ")?.split_once(':')?;
+        let name = head.split_whitespace().next()?.to_owned();
+        let mut fields = tail.split_whitespace();
+        let (position, byte_order) = fields.next()?.split_once('@')?;
+        let (start_bit, bit_len) = position.split_once('|')?;
+        let factors = fields.next()?.trim_start_matches('(').trim_end_matches(')');
+        let (scale, offset) = factors.split_once(',')?;
+        Some(SignalLayout {
+            name,
+            start_bit: start_bit.parse().ok()?,
+            bit_len: bit_len.parse().ok()?,
+            little_endian: byte_order.starts_with('1'),
+            signed: byte_order.ends_with('-'),
+            scale: scale.parse().ok()?,
+            offset: offset.parse().ok()?,
+        })
+    }
+
+    // Write the physical value into data, clamped to what the signal can hold
+    fn encode(&self, value: f64, data: &mut [u8]) -> Result<(), String> {
+        if self.bit_len == 0 || self.bit_len > 64 || self.scale == 0.0 {
+            return Err(format!("signal {} has an unusable layout", self.name));
+        }
+        let raw = ((value - self.offset) / self.scale).round();
+        let (min, max) = match (self.signed, self.bit_len) {
+            (true, len) => (
+                -(2f64.powi(len as i32 - 1)),
+                2f64.powi(len as i32 - 1) - 1.0,
+            ),
+            (false, len) => (0.0, 2f64.powi(len as i32) - 1.0),
+        };
+        let raw = raw.clamp(min, max) as i64 as u64;
+
+        // Bit positions from the LSB up
+        let mut position = if self.little_endian {
+            self.start_bit
+        } else {
+            // Motorola: walk from the MSB down to the LSB in the DBC sawtooth numbering
+            let mut position = self.start_bit;
+            for _ in 1..self.bit_len {
+                position = if position.is_multiple_of(8) {
+                    position + 15
+                } else {
+                    position - 1
+                };
+            }
+            position
+        };
+        let len = data.len();
+        for bit in 0..self.bit_len {
+            let byte = data
+                .get_mut(position / 8)
+                .ok_or_else(|| format!("signal {} doesn't fit in {} bytes", self.name, len))?;
+            if (raw >> bit) & 1 != 0 {
+                *byte |= 1 << (position % 8);
+            } else {
+                *byte &= !(1 << (position % 8));
+            }
+            position = match self.little_endian {
+                true => position + 1,
+                // Next more significant bit, going back up the sawtooth
+                false if position % 8 == 7 => position.wrapping_sub(15),
+                false => position + 1,
+            };
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct CanUtils {
     hash_msg: HashMap<String, u32>,
+    // Data length and signals of every message, by message name
+    hash_dlc: HashMap<String, usize>,
+    hash_signals: HashMap<String, Vec<SignalLayout>>,
     socket_can: CanSocket,
     can_info: PgnLibrary
 }
@@ -20,26 +109,42 @@ pub struct CanUtils {
 impl CanUtils {
     pub fn new(dbcpath: String, canport: &str) -> Result<Self, Box<dyn Error>> {
         let mut hash_msg = HashMap::new();
+        let mut hash_dlc = HashMap::new();
+        let mut hash_signals: HashMap<String, Vec<SignalLayout>> = HashMap::new();
 
-        // Read the DBC file and populate hash_msg
+        // Read the DBC file and populate hash_msg, SG_ lines belong to the BO_ above them
         let file = File::open(&dbcpath)?;
         let reader = BufReader::new(file);
+        let mut message: Option<String> = None;
 
         for line in reader.lines() {
             let line = line?;
             if line.starts_with("BO_ ") {
                 let parts: Vec<&str> = line.split_whitespace().collect();
+                message = None;
                 if parts.len() > 2 {
                     if let Ok(id) = parts[1].parse::<u32>() {
                         let name = parts[2].trim_end_matches(':').to_string();
-                        hash_msg.insert(name, id);
+                        if let Some(Ok(dlc)) = parts.get(3).map(|dlc| dlc.parse::<usize>()) {
+                            hash_dlc.insert(name.clone(), dlc);
+                        }
+                        hash_msg.insert(name.clone(), id);
+                        message = Some(name);
                     }
                 }
+            } else if line.trim_start().starts_with("SG_ ") {
+                if let (Some(name), Some(signal)) = (&message, SignalLayout::parse(&line)) {
+                    hash_signals.entry(name.clone()).or_default().push(signal);
+                }
+            } else if line.trim().is_empty() {
+                message = None;
             }
         }
 
         Ok(CanUtils {
             hash_msg,
+            hash_dlc,
+            hash_signals,
             socket_can: CanSocket::open(canport)?,
             can_info: PgnLibrary::from_dbc_file(dbcpath)?,
         })
@@ -101,6 +206,62 @@ impl CanUtils {
         }
     }
 
+    // Limit how long get_messages() waits for a frame, so callers can serve other events
+    pub fn set_read_timeout(&self, timeout: Duration) -> Result<(), Box<dyn Error>> {
+        self.socket_can.set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    // Send raw data (up to 8 bytes) in the message named can_name in the DBC
+    pub fn send_frame(&self, can_name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let id = match self.hash_msg.get(can_name) {
+            Some(&id) => id & 0x1FFFFFFF, // Apply the extended ID bit
+            None => return Err(format!("CAN name '{}' not found in the database", can_name).into()),
+        };
+        let id = ExtendedId::new(id).ok_or("invalid extended CAN ID")?;
+        let frame = CanFrame::new(id, data).ok_or("CAN frame data longer than 8 bytes")?;
+        self.socket_can.write_frame(&frame)?;
+        Ok(())
+    }
+
+    pub fn has_signal(&self, can_name: &str, signal: &str) -> bool {
+        self.hash_signals
+            .get(can_name)
+            .is_some_and(|signals| signals.iter().any(|layout| layout.name == signal))
+    }
+
+    // Encode physical signal values with the DBC layout of can_name and send the frame.
+    // Signals of the message that aren't given are sent as 0.
+    pub fn send_signals(
+        &self,
+        can_name: &str,
+        values: &[(&str, f64)],
+    ) -> Result<(), Box<dyn Error>> {
+        let data = self.encode_signals(can_name, values)?;
+        self.send_frame(can_name, &data)
+    }
+
+    fn encode_signals(&self, can_name: &str, values: &[(&str, f64)]) -> Result<Vec<u8>, String> {
+        let dlc = *self
+            .hash_dlc
+            .get(can_name)
+            .ok_or_else(|| format!("CAN name '{}' not found in the database", can_name))?;
+        let signals = self
+            .hash_signals
+            .get(can_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut data = vec![0u8; dlc];
+        for (name, value) in values {
+            let layout = signals
+                .iter()
+                .find(|layout| layout.name == *name)
+                .ok_or_else(|| format!("signal '{}' not found in {}", name, can_name))?;
+            layout.encode(*value, &mut data)?;
+        }
+        Ok(data)
+    }
+
     pub fn get_messages(&self) -> Result<HashMap<String, f32>, Box<dyn Error>> {
         let mut result = HashMap::new();
 
@@ -130,6 +291,9 @@ impl CanUtils {
                     }
                 }
             }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                // Read timeout expired without traffic
+            }
             Err(e) => {
                 log::error!("Failed to read CAN frame: {:?}", e);
                 return Err(Box::new(e));
@@ -138,4 +302,57 @@ impl CanUtils {
 
         Ok(result)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_signal_line() {
+        let layout =
+            SignalLayout::parse(r#" SG_ call_reason : 8|8@1+ (1,0) [0|255] "" VCU"#).unwrap();
+        assert_eq!(layout.name, "call_reason");
+        assert_eq!((layout.start_bit, layout.bit_len), (8, 8));
+        assert!(layout.little_endian && !layout.signed);
+        assert_eq!((layout.scale, layout.offset), (1.0, 0.0));
+
+        let layout =
+            SignalLayout::parse(r#" SG_ temp m1 : 7|12@0- (0.1,-40) [-40|150] "C" VCU"#).unwrap();
+        assert_eq!(layout.name, "temp");
+        assert!(!layout.little_endian && layout.signed);
+        assert_eq!((layout.scale, layout.offset), (0.1, -40.0));
+    }
+
+    #[test]
+    fn encode_intel() {
+        let layout = SignalLayout::parse(r#" SG_ value : 4|12@1+ (1,0) [0|4095] "" VCU"#).unwrap();
+        let mut data = [0u8; 3];
+        layout.encode(0xABC as f64, &mut data).unwrap();
+        assert_eq!(data, [0xC0, 0xAB, 0x00]);
+    }
+
+    #[test]
+    fn encode_motorola() {
+        // MSB at bit 7 of byte 0, 16 bits: plain big endian u16 in bytes 0-1
+        let layout = SignalLayout::parse(r#" SG_ value : 7|16@0+ (1,0) [0|65535] "" VCU"#).unwrap();
+        let mut data = [0u8; 2];
+        layout.encode(0x1234 as f64, &mut data).unwrap();
+        assert_eq!(data, [0x12, 0x34]);
+    }
+
+    #[test]
+    fn encode_scaled_signed_and_clamped() {
+        let layout = SignalLayout::parse(r#" SG_ value : 0|8@1- (0.5,0) [-64|63] "" VCU"#).unwrap();
+        let mut data = [0u8; 1];
+        layout.encode(-1.0, &mut data).unwrap();
+        assert_eq!(data, [0xFE]);
+        layout.encode(1000.0, &mut data).unwrap();
+        assert_eq!(data, [0x7F]);
+    }
+
+    #[test]
+    fn encode_out_of_frame() {
+        let layout = SignalLayout::parse(r#" SG_ value : 60|8@1+ (1,0) [0|255] "" VCU"#).unwrap();
+        assert!(layout.encode(1.0, &mut [0u8; 2]).is_err());
+    }
+}
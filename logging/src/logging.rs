@@ -56,6 +56,8 @@ impl MyLogging {
                 Logger::builder()
                     .build("app::requests", LevelFilter::Info),
             )
+            // Remote commands are audited whatever the runtime log level
+            .logger(Logger::builder().build("sms_command", LevelFilter::Info))
             .build(
                 Root::builder()
                     .appender("stdout")
@@ -1 +1,19 @@
 pub mod modem_cli;
+pub mod modem_error;
+pub mod modem_state;
+pub mod modem_events;
+pub mod modem_selector;
+pub mod modem_bearer;
+pub mod modem_signal;
+pub mod modem_location;
+pub mod modem_sim;
+pub mod modem_3gpp;
+pub mod modem_radio;
+pub mod modem_sms;
+pub mod modem_ussd;
+pub mod modem_command;
+pub mod modem_cell;
+pub mod modem_inventory;
+pub mod modem_voice;
+pub mod modem_time;
+pub mod nmea;
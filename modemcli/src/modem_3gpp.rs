@@ -0,0 +1,210 @@
+use crate::modem_cli::{prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use crate::modem_radio::AccessTechnologies;
+use bitflags::bitflags;
+use dbus::arg::{prop_cast, PropMap};
+use std::fmt;
+use std::time::Duration;
+
+const MODEM_3GPP_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Modem3gpp";
+
+// A scan may take minutes depending on the bands to go through
+const SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(120);
+
+// MMModem3gppRegistrationState
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum RegistrationState {
+    Idle = 0,
+    Home = 1,
+    Searching = 2,
+    Denied = 3,
+    Unknown = 4,
+    Roaming = 5,
+    HomeSmsOnly = 6,
+    RoamingSmsOnly = 7,
+    EmergencyOnly = 8,
+    HomeCsfbNotPreferred = 9,
+    RoamingCsfbNotPreferred = 10,
+    AttachedRlos = 11,
+}
+
+impl RegistrationState {
+    pub fn is_registered(self) -> bool {
+        self.is_home() || self.is_roaming()
+    }
+
+    pub fn is_home(self) -> bool {
+        matches!(
+            self,
+            RegistrationState::Home
+                | RegistrationState::HomeSmsOnly
+                | RegistrationState::HomeCsfbNotPreferred
+        )
+    }
+
+    pub fn is_roaming(self) -> bool {
+        matches!(
+            self,
+            RegistrationState::Roaming
+                | RegistrationState::RoamingSmsOnly
+                | RegistrationState::RoamingCsfbNotPreferred
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RegistrationState::Idle => "idle",
+            RegistrationState::Home => "home",
+            RegistrationState::Searching => "searching",
+            RegistrationState::Denied => "denied",
+            RegistrationState::Unknown => "unknown",
+            RegistrationState::Roaming => "roaming",
+            RegistrationState::HomeSmsOnly => "home-sms-only",
+            RegistrationState::RoamingSmsOnly => "roaming-sms-only",
+            RegistrationState::EmergencyOnly => "emergency-only",
+            RegistrationState::HomeCsfbNotPreferred => "home-csfb-not-preferred",
+            RegistrationState::RoamingCsfbNotPreferred => "roaming-csfb-not-preferred",
+            RegistrationState::AttachedRlos => "attached-rlos",
+        }
+    }
+}
+
+impl From<u32> for RegistrationState {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => RegistrationState::Idle,
+            1 => RegistrationState::Home,
+            2 => RegistrationState::Searching,
+            3 => RegistrationState::Denied,
+            5 => RegistrationState::Roaming,
+            6 => RegistrationState::HomeSmsOnly,
+            7 => RegistrationState::RoamingSmsOnly,
+            8 => RegistrationState::EmergencyOnly,
+            9 => RegistrationState::HomeCsfbNotPreferred,
+            10 => RegistrationState::RoamingCsfbNotPreferred,
+            11 => RegistrationState::AttachedRlos,
+            _ => RegistrationState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for RegistrationState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+bitflags! {
+    // MMModem3gppFacility
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct FacilityLocks: u32 {
+        const SIM = 1 << 0;
+        const FIXED_DIALING = 1 << 1;
+        const DEVICE_PH_SIM = 1 << 2;
+        const DEVICE_PH_FSIM = 1 << 3;
+        const NET_PERS = 1 << 4;
+        const NET_SUB_PERS = 1 << 5;
+        const PROVIDER_PERS = 1 << 6;
+        const CORP_PERS = 1 << 7;
+    }
+}
+
+// MMModem3gppNetworkAvailability
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NetworkAvailability {
+    #[default]
+    Unknown,
+    Available,
+    Current,
+    Forbidden,
+}
+
+impl From<u32> for NetworkAvailability {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => NetworkAvailability::Available,
+            2 => NetworkAvailability::Current,
+            3 => NetworkAvailability::Forbidden,
+            _ => NetworkAvailability::Unknown,
+        }
+    }
+}
+
+// One network found by scan_networks()
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkInfo {
+    pub availability: NetworkAvailability,
+    pub operator_long: String,
+    pub operator_short: String,
+    // MCC/MNC, what register_network() expects
+    pub operator_code: String,
+    pub access_technology: AccessTechnologies,
+}
+
+impl NetworkInfo {
+    fn from_props(props: &PropMap) -> Self {
+        let text = |name: &str| {
+            prop_cast::<String>(props, name)
+                .cloned()
+                .unwrap_or_default()
+        };
+        NetworkInfo {
+            availability: NetworkAvailability::from(prop_u64(props, "status").unwrap_or(0) as u32),
+            operator_long: text("operator-long"),
+            operator_short: text("operator-short"),
+            operator_code: text("operator-code"),
+            access_technology: AccessTechnologies::from_bits_truncate(
+                prop_u64(props, "access-technology").unwrap_or(0) as u32,
+            ),
+        }
+    }
+}
+
+impl IonModemCli {
+    pub fn registration_state(&self) -> Result<RegistrationState, ModemError> {
+        let state: u32 = self.get_property(MODEM_3GPP_INTERFACE, "RegistrationState")?;
+        Ok(RegistrationState::from(state))
+    }
+
+    // MCC/MNC of the network the modem is registered on, empty when not registered
+    pub fn operator_code(&self) -> Result<String, ModemError> {
+        self.get_property(MODEM_3GPP_INTERFACE, "OperatorCode")
+    }
+
+    pub fn operator_name(&self) -> Result<String, ModemError> {
+        self.get_property(MODEM_3GPP_INTERFACE, "OperatorName")
+    }
+
+    pub fn enabled_facility_locks(&self) -> Result<FacilityLocks, ModemError> {
+        let locks: u32 = self.get_property(MODEM_3GPP_INTERFACE, "EnabledFacilityLocks")?;
+        Ok(FacilityLocks::from_bits_truncate(locks))
+    }
+
+    // Blocks until the modem is done scanning, which may take minutes
+    pub fn scan_networks(&self) -> Result<Vec<NetworkInfo>, ModemError> {
+        let msg = self.modem_method(MODEM_3GPP_INTERFACE, "Scan")?;
+        let reply = self.call_modem_with_timeout(msg, SCAN_TIMEOUT)?;
+        let networks: Vec<PropMap> = reply.read1()?;
+        Ok(networks.iter().map(NetworkInfo::from_props).collect())
+    }
+
+    // Manual registration on the given MCC/MNC, an empty operator goes back to automatic selection
+    pub fn register_network(&self, operator_id: &str) -> Result<(), ModemError> {
+        let valid = operator_id.is_empty()
+            || ((5..=6).contains(&operator_id.len())
+                && operator_id.chars().all(|c| c.is_ascii_digit()));
+        if !valid {
+            return Err(ModemError::InvalidArgument(format!(
+                "'{}' is not a MCC/MNC",
+                operator_id
+            )));
+        }
+        let msg = self
+            .modem_method(MODEM_3GPP_INTERFACE, "Register")?
+            .append1(operator_id);
+        let _ = self.call_modem_with_timeout(msg, REGISTER_TIMEOUT)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+use crate::modem_cli::{object_path, prop_map, prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use dbus::arg::{prop_cast, PropMap, Variant};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+const BEARER_INTERFACE: &str = "org.freedesktop.ModemManager1.Bearer";
+
+// MMBearerIpFamily
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IpType {
+    Ipv4,
+    Ipv6,
+    Ipv4v6,
+    // Let the modem decide
+    #[default]
+    Any,
+}
+
+impl IpType {
+    pub fn bits(self) -> u32 {
+        match self {
+            IpType::Ipv4 => 1 << 0,
+            IpType::Ipv6 => 1 << 1,
+            IpType::Ipv4v6 => 1 << 2,
+            IpType::Any => 0xFFFF_FFF7,
+        }
+    }
+}
+
+impl FromStr for IpType {
+    type Err = ModemError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ipv4" => Ok(IpType::Ipv4),
+            "ipv6" => Ok(IpType::Ipv6),
+            "ipv4v6" => Ok(IpType::Ipv4v6),
+            "any" => Ok(IpType::Any),
+            _ => Err(ModemError::InvalidArgument(format!(
+                "unknown ip type '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+// MMBearerAllowedAuth, a single method is enough for our APNs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BearerAuth {
+    // Let the modem pick
+    #[default]
+    Auto,
+    None,
+    Pap,
+    Chap,
+    MsChap,
+    MsChapV2,
+    Eap,
+}
+
+impl BearerAuth {
+    pub fn bits(self) -> Option<u32> {
+        match self {
+            BearerAuth::Auto => None,
+            BearerAuth::None => Some(1 << 0),
+            BearerAuth::Pap => Some(1 << 1),
+            BearerAuth::Chap => Some(1 << 2),
+            BearerAuth::MsChap => Some(1 << 3),
+            BearerAuth::MsChapV2 => Some(1 << 4),
+            BearerAuth::Eap => Some(1 << 5),
+        }
+    }
+}
+
+impl FromStr for BearerAuth {
+    type Err = ModemError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(BearerAuth::Auto),
+            "none" => Ok(BearerAuth::None),
+            "pap" => Ok(BearerAuth::Pap),
+            "chap" => Ok(BearerAuth::Chap),
+            "mschap" => Ok(BearerAuth::MsChap),
+            "mschapv2" => Ok(BearerAuth::MsChapV2),
+            "eap" => Ok(BearerAuth::Eap),
+            _ => Err(ModemError::InvalidArgument(format!(
+                "unknown auth method '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+// Settings used by Simple.Connect and CreateBearer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BearerConfig {
+    pub apn: String,
+    pub ip_type: IpType,
+    pub auth: BearerAuth,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub allow_roaming: bool,
+}
+
+impl Default for BearerConfig {
+    fn default() -> Self {
+        BearerConfig {
+            apn: String::new(),
+            ip_type: IpType::Any,
+            auth: BearerAuth::Auto,
+            user: None,
+            password: None,
+            allow_roaming: true,
+        }
+    }
+}
+
+impl BearerConfig {
+    pub(crate) fn to_props(&self) -> PropMap {
+        let mut props: PropMap = HashMap::new();
+        if !self.apn.is_empty() {
+            props.insert("apn".to_owned(), Variant(Box::new(self.apn.clone())));
+        }
+        props.insert("ip-type".to_owned(), Variant(Box::new(self.ip_type.bits())));
+        if let Some(auth) = self.auth.bits() {
+            props.insert("allowed-auth".to_owned(), Variant(Box::new(auth)));
+        }
+        if let Some(user) = &self.user {
+            props.insert("user".to_owned(), Variant(Box::new(user.clone())));
+        }
+        if let Some(password) = &self.password {
+            props.insert("password".to_owned(), Variant(Box::new(password.clone())));
+        }
+        props.insert(
+            "allow-roaming".to_owned(),
+            Variant(Box::new(self.allow_roaming)),
+        );
+        props
+    }
+}
+
+// MMBearerIpMethod
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IpMethod {
+    #[default]
+    Unknown,
+    Ppp,
+    Static,
+    Dhcp,
+}
+
+impl From<u32> for IpMethod {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => IpMethod::Ppp,
+            2 => IpMethod::Static,
+            3 => IpMethod::Dhcp,
+            _ => IpMethod::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IpConfig {
+    pub method: IpMethod,
+    pub address: Option<String>,
+    pub prefix: Option<u32>,
+    pub gateway: Option<String>,
+    pub dns: Vec<String>,
+    pub mtu: Option<u32>,
+}
+
+impl IpConfig {
+    // None when the bearer has no configuration for this IP family
+    fn from_props(props: &PropMap) -> Option<Self> {
+        let method = IpMethod::from(prop_u64(props, "method")? as u32);
+        let dns = ["dns1", "dns2", "dns3"]
+            .iter()
+            .filter_map(|key| prop_cast::<String>(props, key).cloned())
+            .collect();
+        Some(IpConfig {
+            method,
+            address: prop_cast::<String>(props, "address").cloned(),
+            prefix: prop_u64(props, "prefix").map(|prefix| prefix as u32),
+            gateway: prop_cast::<String>(props, "gateway").cloned(),
+            dns,
+            mtu: prop_u64(props, "mtu").map(|mtu| mtu as u32),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BearerStats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    // Seconds since the current connection was established
+    pub duration: u32,
+    pub attempts: u32,
+    pub failed_attempts: u32,
+    pub total_rx_bytes: u64,
+    pub total_tx_bytes: u64,
+    pub total_duration: u32,
+}
+
+impl BearerStats {
+    fn from_props(props: &PropMap) -> Self {
+        let value = |key: &str| prop_u64(props, key).unwrap_or(0);
+        BearerStats {
+            rx_bytes: value("rx-bytes"),
+            tx_bytes: value("tx-bytes"),
+            duration: value("duration") as u32,
+            attempts: value("attempts") as u32,
+            failed_attempts: value("failed-attempts") as u32,
+            total_rx_bytes: value("total-rx-bytes"),
+            total_tx_bytes: value("total-tx-bytes"),
+            total_duration: value("total-duration") as u32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BearerStatus {
+    pub path: String,
+    pub connected: bool,
+    // Network interface, e.g. "wwan0", empty while disconnected
+    pub interface: String,
+    pub ipv4: Option<IpConfig>,
+    pub ipv6: Option<IpConfig>,
+    pub stats: BearerStats,
+}
+
+impl BearerStatus {
+    fn from_props(path: &str, props: &PropMap) -> Self {
+        BearerStatus {
+            path: path.to_owned(),
+            connected: prop_cast::<bool>(props, "Connected")
+                .copied()
+                .unwrap_or(false),
+            interface: prop_cast::<String>(props, "Interface")
+                .cloned()
+                .unwrap_or_default(),
+            ipv4: IpConfig::from_props(&prop_map(props, "Ip4Config")),
+            ipv6: IpConfig::from_props(&prop_map(props, "Ip6Config")),
+            stats: BearerStats::from_props(&prop_map(props, "Stats")),
+        }
+    }
+}
+
+impl IonModemCli {
+    // Bring data up with Modem.Simple.Connect, returns the bearer object path
+    pub fn connect_data(&self, config: &BearerConfig) -> Result<String, ModemError> {
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem.Simple", "Connect")?
+            .append1(config.to_props());
+        let reply = self.call_modem(msg)?;
+        let bearer: dbus::Path = reply.read1()?;
+        Ok(bearer.to_string())
+    }
+
+    // Tear down one bearer, or every bearer of the modem when None
+    pub fn disconnect_data(&self, bearer: Option<&str>) -> Result<(), ModemError> {
+        let bearer = object_path(bearer.unwrap_or("/"))?;
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem.Simple", "Disconnect")?
+            .append1(bearer);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn create_bearer(&self, config: &BearerConfig) -> Result<String, ModemError> {
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "CreateBearer")?
+            .append1(config.to_props());
+        let reply = self.call_modem(msg)?;
+        let bearer: dbus::Path = reply.read1()?;
+        Ok(bearer.to_string())
+    }
+
+    pub fn delete_bearer(&self, bearer: &str) -> Result<(), ModemError> {
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "DeleteBearer")?
+            .append1(object_path(bearer)?);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn list_bearers(&self) -> Result<Vec<String>, ModemError> {
+        let bearers: Vec<dbus::Path<'static>> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "Bearers")?;
+        Ok(bearers.iter().map(|path| path.to_string()).collect())
+    }
+
+    // Connect a bearer previously made with create_bearer()
+    pub fn connect_bearer(&self, bearer: &str) -> Result<(), ModemError> {
+        let _ = self.call_object(bearer, BEARER_INTERFACE, "Connect")?;
+        Ok(())
+    }
+
+    pub fn disconnect_bearer(&self, bearer: &str) -> Result<(), ModemError> {
+        let _ = self.call_object(bearer, BEARER_INTERFACE, "Disconnect")?;
+        Ok(())
+    }
+
+    pub fn bearer_status(&self, bearer: &str) -> Result<BearerStatus, ModemError> {
+        let props = self.get_all_properties(bearer, BEARER_INTERFACE)?;
+        Ok(BearerStatus::from_props(bearer, &props))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::message::Message;
+
+    fn variant<T: dbus::arg::RefArg + 'static>(value: T) -> Variant<Box<dyn dbus::arg::RefArg>> {
+        Variant(Box::new(value))
+    }
+
+    // Bearer GetAll reply as it comes off the bus, nested a{sv} dicts included
+    fn bearer_reply() -> PropMap {
+        let mut ip4: PropMap = HashMap::new();
+        ip4.insert("method".to_owned(), variant(3u32));
+        ip4.insert("address".to_owned(), variant("10.64.12.7".to_owned()));
+        ip4.insert("prefix".to_owned(), variant(30u32));
+        ip4.insert("gateway".to_owned(), variant("10.64.12.8".to_owned()));
+        ip4.insert("dns1".to_owned(), variant("8.8.8.8".to_owned()));
+        ip4.insert("dns2".to_owned(), variant("8.8.4.4".to_owned()));
+        ip4.insert("mtu".to_owned(), variant(1500u32));
+        let mut stats: PropMap = HashMap::new();
+        stats.insert("rx-bytes".to_owned(), variant(1024u64));
+        stats.insert("tx-bytes".to_owned(), variant(512u64));
+        stats.insert("duration".to_owned(), variant(60u32));
+
+        let mut props: PropMap = HashMap::new();
+        props.insert("Connected".to_owned(), variant(true));
+        props.insert("Interface".to_owned(), variant("wwan0".to_owned()));
+        props.insert("Ip4Config".to_owned(), variant(ip4));
+        props.insert("Ip6Config".to_owned(), variant(PropMap::new()));
+        props.insert("Stats".to_owned(), variant(stats));
+
+        let msg = Message::new_signal(
+            "/org/freedesktop/ModemManager1/Bearer/0",
+            "org.test",
+            "Reply",
+        )
+        .unwrap()
+        .append1(props);
+        msg.read1().unwrap()
+    }
+
+    #[test]
+    fn bearer_status_from_reply() {
+        let status =
+            BearerStatus::from_props("/org/freedesktop/ModemManager1/Bearer/0", &bearer_reply());
+        assert!(status.connected);
+        assert_eq!(status.interface, "wwan0");
+        assert_eq!(
+            status.ipv4,
+            Some(IpConfig {
+                method: IpMethod::Dhcp,
+                address: Some("10.64.12.7".to_owned()),
+                prefix: Some(30),
+                gateway: Some("10.64.12.8".to_owned()),
+                dns: vec!["8.8.8.8".to_owned(), "8.8.4.4".to_owned()],
+                mtu: Some(1500),
+            })
+        );
+        assert_eq!(status.ipv6, None);
+        assert_eq!(status.stats.rx_bytes, 1024);
+        assert_eq!(status.stats.tx_bytes, 512);
+        assert_eq!(status.stats.duration, 60);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::modem_cli::{prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use dbus::arg::{prop_cast, PropMap};
+use std::fmt;
+
+// MMCellType
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CellType {
+    #[default]
+    Unknown,
+    Cdma,
+    Gsm,
+    Umts,
+    Tdscdma,
+    Lte,
+    Nr5g,
+}
+
+impl From<u32> for CellType {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => CellType::Cdma,
+            2 => CellType::Gsm,
+            3 => CellType::Umts,
+            4 => CellType::Tdscdma,
+            5 => CellType::Lte,
+            6 => CellType::Nr5g,
+            _ => CellType::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for CellType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rat = match self {
+            CellType::Unknown => "unknown",
+            CellType::Cdma => "cdma",
+            CellType::Gsm => "gsm",
+            CellType::Umts => "umts",
+            CellType::Tdscdma => "tdscdma",
+            CellType::Lte => "lte",
+            CellType::Nr5g => "5gnr",
+        };
+        f.write_str(rat)
+    }
+}
+
+// One serving or neighbor cell from Modem.GetCellInfo, None for what the modem didn't report
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CellInfo {
+    pub serving: bool,
+    pub rat: CellType,
+    // MCC/MNC
+    pub operator_id: String,
+    pub ci: Option<u64>,
+    // Physical cell id (LTE/NR) or primary scrambling code (UMTS)
+    pub pci: Option<u32>,
+    pub tac: Option<u32>,
+    pub lac: Option<u32>,
+    // EARFCN on LTE, NR-ARFCN on 5G, UARFCN on UMTS, ARFCN on GSM
+    pub arfcn: Option<u32>,
+    pub rsrp: Option<f64>,
+    pub rsrq: Option<f64>,
+    pub sinr: Option<f64>,
+}
+
+impl CellInfo {
+    fn from_props(props: &PropMap) -> Self {
+        // Identifiers are hex strings
+        let hex = |name: &str| {
+            prop_cast::<String>(props, name).and_then(|value| u64::from_str_radix(value, 16).ok())
+        };
+        let number = |name: &str| prop_u64(props, name).map(|value| value as u32);
+        let value = |name: &str| props.get(name).and_then(|value| value.0.as_f64());
+
+        let rat = CellType::from(number("cell-type").unwrap_or(0));
+        let arfcn = match rat {
+            CellType::Lte => number("earfcn"),
+            CellType::Nr5g => number("nrarfcn"),
+            CellType::Umts => number("uarfcn"),
+            _ => number("arfcn"),
+        };
+        let pci = match rat {
+            CellType::Umts => number("psc"),
+            _ => hex("physical-ci").map(|pci| pci as u32),
+        };
+        CellInfo {
+            serving: prop_u64(props, "serving").unwrap_or(0) != 0,
+            rat,
+            operator_id: prop_cast::<String>(props, "operator-id")
+                .cloned()
+                .unwrap_or_default(),
+            ci: hex("ci"),
+            pci,
+            tac: hex("tac").map(|tac| tac as u32),
+            lac: hex("lac").map(|lac| lac as u32),
+            arfcn,
+            rsrp: value("rsrp"),
+            rsrq: value("rsrq"),
+            sinr: value("sinr"),
+        }
+    }
+}
+
+impl IonModemCli {
+    // Serving cell(s) first, then neighbors
+    pub fn cell_info(&self) -> Result<Vec<CellInfo>, ModemError> {
+        let msg = self.modem_method("org.freedesktop.ModemManager1.Modem", "GetCellInfo")?;
+        let reply = self.call_modem(msg)?;
+        let cells: Vec<PropMap> = reply.read1()?;
+        let mut cells: Vec<CellInfo> = cells.iter().map(CellInfo::from_props).collect();
+        cells.sort_by_key(|cell| !cell.serving);
+        Ok(cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::{RefArg, Variant};
+
+    fn cell(entries: Vec<(&str, Box<dyn RefArg>)>) -> CellInfo {
+        let props: PropMap = entries
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), Variant(value)))
+            .collect();
+        CellInfo::from_props(&props)
+    }
+
+    #[test]
+    fn serving_lte_cell() {
+        let info = cell(vec![
+            ("cell-type", Box::new(5u32)),
+            ("serving", Box::new(true)),
+            ("operator-id", Box::new("20801".to_owned())),
+            ("ci", Box::new("0C3D4E5".to_owned())),
+            ("physical-ci", Box::new("1F".to_owned())),
+            ("tac", Box::new("1A2B".to_owned())),
+            ("earfcn", Box::new(6300u32)),
+            ("arfcn", Box::new(12u32)),
+            ("rsrp", Box::new(-95.5f64)),
+            ("rsrq", Box::new(-11.0f64)),
+            ("sinr", Box::new(7.25f64)),
+        ]);
+        assert!(info.serving);
+        assert_eq!(info.rat, CellType::Lte);
+        assert_eq!(info.operator_id, "20801");
+        assert_eq!(info.ci, Some(0x0C3D4E5));
+        assert_eq!(info.pci, Some(0x1F));
+        assert_eq!(info.tac, Some(0x1A2B));
+        assert_eq!(info.lac, None);
+        assert_eq!(info.arfcn, Some(6300));
+        assert_eq!(info.rsrp, Some(-95.5));
+        assert_eq!(info.rsrq, Some(-11.0));
+        assert_eq!(info.sinr, Some(7.25));
+    }
+
+    #[test]
+    fn umts_cell_uses_psc_and_uarfcn() {
+        let info = cell(vec![
+            ("cell-type", Box::new(3u32)),
+            ("serving", Box::new(false)),
+            ("lac", Box::new("FFFE".to_owned())),
+            ("psc", Box::new(301u32)),
+            ("physical-ci", Box::new("12".to_owned())),
+            ("uarfcn", Box::new(10788u32)),
+        ]);
+        assert!(!info.serving);
+        assert_eq!(info.rat, CellType::Umts);
+        assert_eq!(info.lac, Some(0xFFFE));
+        assert_eq!(info.pci, Some(301));
+        assert_eq!(info.arfcn, Some(10788));
+    }
+
+    #[test]
+    fn nr_and_gsm_arfcn() {
+        let nr = cell(vec![
+            ("cell-type", Box::new(6u32)),
+            ("nrarfcn", Box::new(627264u32)),
+            ("arfcn", Box::new(1u32)),
+        ]);
+        assert_eq!(nr.rat, CellType::Nr5g);
+        assert_eq!(nr.arfcn, Some(627264));
+        let gsm = cell(vec![
+            ("cell-type", Box::new(2u32)),
+            ("arfcn", Box::new(62u32)),
+        ]);
+        assert_eq!(gsm.rat, CellType::Gsm);
+        assert_eq!(gsm.arfcn, Some(62));
+    }
+
+    #[test]
+    fn bad_identifiers_are_dropped() {
+        let info = cell(vec![
+            ("ci", Box::new("not hex".to_owned())),
+            ("tac", Box::new(String::new())),
+        ]);
+        assert_eq!(info.rat, CellType::Unknown);
+        assert_eq!(info.ci, None);
+        assert_eq!(info.tac, None);
+        assert!(!info.serving);
+        assert_eq!(cell(Vec::new()), CellInfo::default());
+    }
+
+    #[test]
+    fn unknown_cell_type() {
+        assert_eq!(CellType::from(0), CellType::Unknown);
+        assert_eq!(CellType::from(7), CellType::Unknown);
+        assert_eq!(CellType::Nr5g.to_string(), "5gnr");
+    }
+}
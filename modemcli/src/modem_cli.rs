@@ -1,21 +1,58 @@
-use dbus::arg::messageitem::MessageItem;
+use dbus::arg::{ArgType, PropMap, RefArg, Variant};
 use dbus::blocking::stdintf::org_freedesktop_dbus::ObjectManager;
 use dbus::blocking::BlockingSender;
 use dbus::blocking::Connection;
+use dbus::channel::Channel;
 use dbus::message::Message;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use std::error::Error;
 use dbus::blocking::Proxy;
 use std::collections::HashMap;
-use dbus::arg::RefArg;
-use log::{debug, trace, error, info, warn};
+use log::{debug, info, trace, warn};
+use crate::modem_command::AtPolicy;
+use crate::modem_error::ModemError;
+use crate::modem_events::EventHandler;
+use crate::modem_location::{Location, LocationConfig, LocationSources};
+use crate::modem_selector::ModemSelector;
+use crate::modem_state::{ModemState, PowerState, StateFailedReason};
+use crate::nmea::GnssFix;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum BusType {
+    #[default]
+    System,
+    Session,
+    // Private bus reachable at a D-Bus address, e.g. "unix:path=/tmp/test_bus"
+    Address(String),
+}
+
+type ManagedObjects = HashMap<dbus::Path<'static>, HashMap<String, PropMap>>;
+
+// Enabling powers the radio up and waits for the SIM, well past the default timeout
+const ENABLE_TIMEOUT: Duration = Duration::from_secs(60);
+const POWER_STATE_TIMEOUT: Duration = Duration::from_secs(30);
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct IonModemCli {
-    destination: String,
-    object: String,
+    pub(crate) destination: String,
+    pub(crate) object: String,
     modem: String,
     ready: bool,
+    selector: ModemSelector,
+    timeout: Duration,
+    bus: BusType,
+    // Raw AT commands allowed through at_command()
+    at_policy: AtPolicy,
+    // Shared connection, opened lazily and re-opened when the bus drops it
+    connection: RefCell<Option<Rc<Connection>>>,
+    // Connection given to the builder, taken on first use instead of opening one
+    injected: RefCell<Option<Connection>>,
+    // Event subscribers, re-attached to every new connection
+    pub(crate) handlers: RefCell<Vec<EventHandler>>,
+    // Modem paths reported gone by ObjectManager or by failing calls
+    pub(crate) lost_modems: Arc<Mutex<Vec<String>>>,
 }
 
 impl Default for IonModemCli {
@@ -25,6 +62,154 @@ impl Default for IonModemCli {
             object: "/org/freedesktop/ModemManager1".to_owned(),
             modem: String::new(),
             ready: false,
+            selector: ModemSelector::First,
+            timeout: Duration::from_millis(2000),
+            bus: BusType::System,
+            at_policy: AtPolicy::default(),
+            connection: RefCell::new(None),
+            injected: RefCell::new(None),
+            handlers: RefCell::new(Vec::new()),
+            lost_modems: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+// A clone talks to the same modem over the same connection. Subscriptions stay with the
+// client that made them, a connection injected through the builder and not used yet too.
+impl Clone for IonModemCli {
+    fn clone(&self) -> Self {
+        IonModemCli {
+            destination: self.destination.clone(),
+            object: self.object.clone(),
+            modem: self.modem.clone(),
+            ready: self.ready,
+            selector: self.selector.clone(),
+            timeout: self.timeout,
+            bus: self.bus.clone(),
+            at_policy: self.at_policy.clone(),
+            connection: RefCell::new(self.connection.borrow().clone()),
+            injected: RefCell::new(None),
+            handlers: RefCell::new(Vec::new()),
+            lost_modems: Arc::clone(&self.lost_modems),
+        }
+    }
+}
+
+// Clients are compared by the modem they target, as before the connection was kept
+impl PartialEq for IonModemCli {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for IonModemCli {}
+
+impl PartialOrd for IonModemCli {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IonModemCli {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.destination, &self.object, &self.modem, self.ready).cmp(&(
+            &other.destination,
+            &other.object,
+            &other.modem,
+            other.ready,
+        ))
+    }
+}
+
+impl fmt::Debug for IonModemCli {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IonModemCli")
+            .field("destination", &self.destination)
+            .field("object", &self.object)
+            .field("modem", &self.modem)
+            .field("ready", &self.ready)
+            .field("selector", &self.selector)
+            .field("timeout", &self.timeout)
+            .field("bus", &self.bus)
+            .field("connected", &self.is_connected())
+            .field("subscribers", &self.handlers.borrow().len())
+            .finish()
+    }
+}
+
+#[derive(Default)]
+pub struct IonModemCliBuilder {
+    destination: Option<String>,
+    object: Option<String>,
+    modem: Option<String>,
+    selector: ModemSelector,
+    timeout: Option<Duration>,
+    bus: BusType,
+    at_policy: AtPolicy,
+    connection: Option<Connection>,
+}
+
+impl IonModemCliBuilder {
+    pub fn destination(mut self, destination: &str) -> Self {
+        self.destination = Some(destination.to_owned());
+        self
+    }
+
+    pub fn object(mut self, object: &str) -> Self {
+        self.object = Some(object.to_owned());
+        self
+    }
+
+    pub fn modem(mut self, modem: &str) -> Self {
+        self.modem = Some(modem.to_owned());
+        self
+    }
+
+    // Rule used to pick the modem when several are present
+    pub fn selector(mut self, selector: ModemSelector) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Bus used for the first connection and for every reconnect
+    pub fn bus(mut self, bus: BusType) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    pub fn at_policy(mut self, policy: AtPolicy) -> Self {
+        self.at_policy = policy;
+        self
+    }
+
+    // Inject an already opened connection (session bus, private test bus...).
+    // If it drops, the client reconnects using the configured bus type.
+    pub fn connection(mut self, connection: Connection) -> Self {
+        connection.set_signal_match_mode(true);
+        self.connection = Some(connection);
+        self
+    }
+
+    pub fn build(self) -> IonModemCli {
+        let default = IonModemCli::default();
+        IonModemCli {
+            destination: self
+                .destination
+                .unwrap_or_else(|| default.destination.clone()),
+            object: self.object.unwrap_or_else(|| default.object.clone()),
+            modem: self.modem.unwrap_or_else(|| default.modem.clone()),
+            ready: false,
+            selector: self.selector,
+            timeout: self.timeout.unwrap_or(default.timeout),
+            bus: self.bus,
+            at_policy: self.at_policy,
+            injected: RefCell::new(self.connection),
+            ..default
         }
     }
 }
@@ -36,183 +221,341 @@ impl IonModemCli {
             object,
             modem,
             ready,
+            ..Default::default()
         }
     }
 
-    fn modem_preparing(&mut self) -> bool {
-        match self.modem_path_detection() {
-            Ok(_modempath) => {
-                self.modem = _modempath;
-                return true;
-            }
-            _ => {return false}
+    pub fn builder() -> IonModemCliBuilder {
+        IonModemCliBuilder::default()
+    }
+
+    // Builder for a client configured like this one, it opens its own connection.
+    // That's how another thread gets a client, IonModemCli itself isn't Send.
+    pub fn to_builder(&self) -> IonModemCliBuilder {
+        IonModemCliBuilder {
+            destination: Some(self.destination.clone()),
+            object: Some(self.object.clone()),
+            modem: None,
+            selector: self.selector.clone(),
+            timeout: Some(self.timeout),
+            bus: self.bus.clone(),
+            at_policy: self.at_policy.clone(),
+            connection: None,
         }
     }
 
-    fn get_modem_properties(&self, object: &str, prop: &str) -> Result<Vec<MessageItem>, Box<dyn Error>> {
-        // Connect to the system bus
-        let conn = Connection::new_system()?;
+    pub fn is_connected(&self) -> bool {
+        match (
+            self.connection.borrow().as_ref(),
+            self.injected.borrow().as_ref(),
+        ) {
+            (Some(conn), _) => conn.channel().is_connected(),
+            (None, Some(conn)) => conn.channel().is_connected(),
+            (None, None) => false,
+        }
+    }
 
-        let interface = "org.freedesktop.DBus.Properties";
+    fn open_connection(&self) -> Result<Connection, dbus::Error> {
+        if let Some(conn) = self.injected.borrow_mut().take() {
+            return Ok(conn);
+        }
+        let conn = match &self.bus {
+            BusType::System => Connection::new_system()?,
+            BusType::Session => Connection::new_session()?,
+            BusType::Address(address) => {
+                let mut channel = Channel::open_private(address)?;
+                channel.register()?;
+                Connection::from(channel)
+            }
+        };
+        // Several subscribers may match the same signal
+        conn.set_signal_match_mode(true);
+        Ok(conn)
+    }
 
-        // Prepare the D-Bus message to get the Enabled property
-        let msg = Message::new_method_call(&self.destination, &self.modem, interface, "Get")?
-            .append2(object, prop);
+    // Return the shared connection, (re)connecting if there is none or the bus dropped it
+    pub(crate) fn connection(&self) -> Result<Rc<Connection>, dbus::Error> {
+        if let Some(conn) = self.connection.borrow().as_ref() {
+            if conn.channel().is_connected() {
+                return Ok(Rc::clone(conn));
+            }
+            warn!("D-Bus connection lost, reconnecting to {:?} bus", self.bus);
+        }
 
-        // Send the message and await the response
-        let reply = conn.send_with_reply_and_block(msg, Duration::from_secs(2))?;
-        trace!("{:?}", reply);
-        let enabled_variant = reply.get_items();
-        trace!("{:?}", enabled_variant);
-        
-        Ok(enabled_variant)
+        let conn = Rc::new(self.open_connection()?);
+        debug!("Connected to {:?} bus as {}", self.bus, conn.unique_name());
+        *self.connection.borrow_mut() = Some(Rc::clone(&conn));
+
+        // Signal matches belong to the old connection, attach them again
+        if let Err(e) = self.track_modem_removal(&conn) {
+            warn!("Can't track modem removal: {}", e);
+        }
+        for handler in self.handlers.borrow().iter() {
+            if let Err(e) = self.add_event_matches(&conn, handler) {
+                warn!("Can't restore modem event subscription: {}", e);
+            }
+        }
+        Ok(conn)
     }
 
-    fn modem_path_detection(&self) -> Result<String, Box<dyn Error>> {
-        // Initialize modempath as an empty string
-        let mut modempath: String = String::new();
+    // Send a method call on the shared connection. A failed call on a dead
+    // connection drops it so the next call starts from a fresh one.
+    pub(crate) fn send_message(&self, msg: Message) -> Result<Message, dbus::Error> {
+        self.send_message_with_timeout(msg, self.timeout)
+    }
 
-        // Connect to the D-Bus system bus
-        let connection = Connection::new_system()?;
+    // For the few calls that outlast the default timeout, e.g. a network scan
+    pub(crate) fn send_message_with_timeout(
+        &self,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, dbus::Error> {
+        let conn = self.connection()?;
+        let reply = conn.send_with_reply_and_block(msg, timeout);
+        if reply.is_err() && !conn.channel().is_connected() {
+            self.connection.borrow_mut().take();
+        }
+        reply
+    }
 
-        // Get managed objects
-        let proxy: Proxy<&Connection> = connection.with_proxy(&self.destination, &self.object, Duration::from_millis(5000));
-        let managed_objects: HashMap<dbus::Path<'_>, HashMap<String, HashMap<String, dbus::arg::Variant<Box<dyn RefArg>>>>>
-            = proxy.get_managed_objects()?;
-
-        // Iterate over the managed objects and find the modem objects
-        for (path, interfaces) in managed_objects {
-            if interfaces.contains_key("org.freedesktop.ModemManager1.Modem") {
-                modempath = path.to_string();
-                break; // Stop after finding the first modem
+    fn modem_preparing(&mut self) -> bool {
+        match self.modem_path_detection() {
+            Ok(modempath) => {
+                info!("Using modem {}", modempath);
+                self.modem = modempath;
+                true
+            }
+            Err(e) => {
+                debug!("Modem not found: {}", e);
+                false
             }
         }
+    }
 
-        Ok(modempath)
-    }
-
-    pub fn is_location_enabled(&self) -> bool {
-        match self.get_modem_properties("org.freedesktop.ModemManager1.Modem.Location", "Enabled") {
-            Ok(results) => {
-                for result in results.iter() {
-                    trace!("{:?}", result);
-                    match result {
-                        MessageItem::Variant(ret_variant) => {
-                            let MessageItem::UInt32(locationmask) = **ret_variant else { return false };
-                            trace!("Mask: {}", locationmask);
-                            return (locationmask & 4) != 0;
-                        },
-                        _ => {return false}
-                    }
-                }
-            }
-            _ => {return false}
+    // Object path of the modem the client is bound to
+    pub fn modem_path(&self) -> Result<&str, ModemError> {
+        if self.modem.is_empty() {
+            return Err(ModemError::NoModem);
         }
-        false
-    }
-
-    pub fn is_modem_enabled(&self) -> bool {
-        match self.get_modem_properties("org.freedesktop.ModemManager1.Modem", "State") {
-            Ok(results) => {
-                for result in results.iter() {
-                    trace!("{:?}", result);
-                    match result {
-                        MessageItem::Variant(ret_variant) => {
-                            let MessageItem::Int32(modemmask) = **ret_variant else { return false };
-                            return (modemmask & 8) != 0;
-                        },
-                        _ => {return false}
-                    }
+        Ok(&self.modem)
+    }
+
+    // Prepare a method call on the current modem object
+    pub(crate) fn modem_method(
+        &self,
+        interface: &str,
+        method: &str,
+    ) -> Result<Message, ModemError> {
+        Message::new_method_call(&self.destination, self.modem_path()?, interface, method)
+            .map_err(ModemError::InvalidArgument)
+    }
+
+    pub(crate) fn call_modem(&self, msg: Message) -> Result<Message, ModemError> {
+        self.call_modem_with_timeout(msg, self.timeout)
+    }
+
+    pub(crate) fn call_modem_with_timeout(
+        &self,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, ModemError> {
+        let on_modem = msg
+            .path()
+            .is_some_and(|path| !self.modem.is_empty() && *path == *self.modem);
+        match self.send_message_with_timeout(msg, timeout) {
+            Ok(reply) => {
+                trace!("{:?}", reply);
+                Ok(reply)
+            }
+            Err(e) => {
+                // The modem object vanished under us (reset, USB re-enumeration...)
+                if on_modem && e.name() == Some("org.freedesktop.DBus.Error.UnknownObject") {
+                    self.mark_modem_lost(&self.modem);
                 }
+                Err(e.into())
             }
-            _ => {return false}
         }
+    }
 
-        false
-    }
-
-    pub fn get_signal_quality(&self) -> u32 {
-        // self.get_modem_properties("org.freedesktop.ModemManager1.Modem", "SignalQuality")
-        0
-    }
-
-    pub fn get_signal_strength(&self) -> f32 {
-        match self.get_modem_properties("org.freedesktop.ModemManager1.Modem.Signal", "Lte") {
-            Ok(results) => {
-                for result in results.iter() {
-                    match result {
-                        MessageItem::Variant(ret_variant) => {
-                            if let MessageItem::Dict(ref dict) = **ret_variant {
-                                let a = dict.to_vec();
-                                for (x, y) in a {
-                                    if x == "rsrp".into() {
-                                        match y {
-                                            MessageItem::Variant(rsrpval) => {
-                                                let MessageItem::Double(rsrpret) = *rsrpval else { return 0.0 };
-                                                return rsrpret as f32;
-                                            }
-                                            _ => {return 0.0}
-                                        }
-                                    }
-                                }
-                            }
-        
-                        },
-                        _ => { return 0.0}
-                    }
-                }
+    pub(crate) fn mark_modem_lost(&self, path: &str) {
+        if let Ok(mut lost) = self.lost_modems.lock() {
+            lost.push(path.to_owned());
+        }
+    }
+
+    // Drop the current modem path if it was reported gone since the last check
+    fn check_modem_lost(&mut self) {
+        let lost: Vec<String> = match self.lost_modems.lock() {
+            Ok(mut lost) => lost.drain(..).collect(),
+            Err(_) => return,
+        };
+        if !self.modem.is_empty() && lost.contains(&self.modem) {
+            warn!("Modem {} lost, waiting for it to re-appear", self.modem);
+            self.modem.clear();
+            self.ready = false;
+        }
+    }
+
+    // Read one property of the modem object, checking its D-Bus type
+    pub(crate) fn get_property<T>(&self, interface: &str, prop: &str) -> Result<T, ModemError>
+    where
+        T: for<'b> dbus::arg::Get<'b>,
+    {
+        let msg = self
+            .modem_method("org.freedesktop.DBus.Properties", "Get")?
+            .append2(interface, prop);
+        let reply = self.call_modem(msg)?;
+        match reply.read1::<Variant<T>>() {
+            Ok(value) => Ok(value.0),
+            Err(_) => Err(ModemError::unexpected_type(
+                &format!("{}.{}", interface, prop),
+                std::any::type_name::<T>(),
+            )),
+        }
+    }
+
+    // Method call on another ModemManager object (bearer, SIM, SMS...)
+    pub(crate) fn object_method(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+    ) -> Result<Message, ModemError> {
+        Message::new_method_call(&self.destination, path, interface, method)
+            .map_err(ModemError::InvalidArgument)
+    }
+
+    pub(crate) fn call_object(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+    ) -> Result<Message, ModemError> {
+        let msg = self.object_method(path, interface, method)?;
+        let reply = self.send_message(msg)?;
+        trace!("{:?}", reply);
+        Ok(reply)
+    }
+
+    pub(crate) fn get_all_properties(
+        &self,
+        path: &str,
+        interface: &str,
+    ) -> Result<PropMap, ModemError> {
+        let msg = self
+            .object_method(path, "org.freedesktop.DBus.Properties", "GetAll")?
+            .append1(interface);
+        let reply = self.send_message(msg)?;
+        Ok(reply.read1()?)
+    }
+
+    pub(crate) fn managed_objects(
+        &self,
+    ) -> Result<HashMap<String, HashMap<String, PropMap>>, ModemError> {
+        let connection = self.connection()?;
+
+        // Get managed objects
+        let proxy: Proxy<&Connection> =
+            connection.with_proxy(&self.destination, &self.object, Duration::from_millis(5000));
+        let managed_objects: ManagedObjects = proxy.get_managed_objects()?;
+
+        Ok(managed_objects
+            .into_iter()
+            .map(|(path, interfaces)| (path.to_string(), interfaces))
+            .collect())
+    }
+
+    fn modem_path_detection(&self) -> Result<String, ModemError> {
+        // Take the first modem matching the selector
+        for modem in self.list_modems()? {
+            if self.selector.matches(&modem) {
+                return Ok(modem.path);
             }
-            _ => {}
         }
 
-        0.0
+        Err(ModemError::NoModem)
+    }
+
+    pub fn selector(&self) -> &ModemSelector {
+        &self.selector
     }
 
-    pub fn get_location(&self) -> String {
-        let mut nmea_str: String = String::new();
-        if self.is_location_enabled() {
-            // Connect to the system bus
-            let c = Connection::new_system().expect("D-Bus connection failed");
+    pub fn at_policy(&self) -> &AtPolicy {
+        &self.at_policy
+    }
 
+    pub fn set_at_policy(&mut self, policy: AtPolicy) {
+        self.at_policy = policy;
+    }
+
+    // Bind to another modem on the next waiting_for_ready()
+    pub fn set_selector(&mut self, selector: ModemSelector) {
+        self.selector = selector;
+        self.modem.clear();
+        self.ready = false;
+    }
+
+    // True when the modem runs exactly this location configuration
+    pub fn is_location_enabled(&self, config: &LocationConfig) -> Result<bool, ModemError> {
+        let current = self.location_config()?;
+        trace!("Location config: {:?}", current);
+        Ok(current == *config)
+    }
+
+    pub fn state(&self) -> Result<ModemState, ModemError> {
+        let state: i32 = self.get_property("org.freedesktop.ModemManager1.Modem", "State")?;
+        Ok(ModemState::from(state))
+    }
+
+    pub fn failed_reason(&self) -> Result<StateFailedReason, ModemError> {
+        let reason: u32 =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "StateFailedReason")?;
+        Ok(StateFailedReason::from(reason))
+    }
+
+    pub fn is_modem_enabled(&self) -> Result<bool, ModemError> {
+        Ok(self.state()?.is_enabled())
+    }
+
+    // LTE RSRP in dBm, None when the modem did not report one
+    pub fn get_signal_strength(&self) -> Result<Option<f32>, ModemError> {
+        let lte: PropMap =
+            self.get_property("org.freedesktop.ModemManager1.Modem.Signal", "Lte")?;
+        match lte.get("rsrp") {
+            Some(rsrp) => match rsrp.0.as_f64() {
+                Some(rsrpret) => Ok(Some(rsrpret as f32)),
+                None => Err(ModemError::unexpected_type("Signal.Lte.rsrp", "f64")),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // One entry per enabled source, empty when location gathering is off
+    pub fn get_location(&self) -> Result<Location, ModemError> {
+        let mut location = Location::default();
+        if !self.location_sources()?.is_empty() {
             // Specify the interface and method to call for getting location
             let interface = "org.freedesktop.ModemManager1.Modem.Location";
 
             // Prepare the D-Bus message
             let gpsmethod = "GetLocation";
-
-            let msg =
-                Message::new_method_call(&self.destination, &self.modem, interface, gpsmethod)
-                    .expect("Failed to create method call");
+            let msg = self.modem_method(interface, gpsmethod)?;
 
             // Send the message and await the response
-            let reply = c.send_with_reply_and_block(msg, Duration::from_secs(2));
-            match reply {
-                Ok(result) => {
-                    // Parse the response to get the Args
-                    let responds: Vec<MessageItem> = result.get_items();
-                    for respond in responds.iter() {
-                        if let MessageItem::Dict(dict) = respond {
-                            let a = dict.to_vec();
-                            for (x, y) in a {
-                                if let MessageItem::UInt32(id) = x {
-                                    if id == 4 {
-                                        if let MessageItem::Variant(var) = y {
-                                            if let MessageItem::Str(nmea) = *var {
-                                                nmea_str = nmea;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {}
+            let reply = self.call_modem(msg)?;
+            let locations: HashMap<u32, Variant<Box<dyn RefArg>>> = reply.read1()?;
+            for (source, value) in locations.iter() {
+                location.add_source(*source, &value.0);
             }
-
         }
 
-        nmea_str
+        Ok(location)
+    }
+
+    // Current GNSS position decoded from the NMEA location source
+    pub fn get_fix(&self) -> Result<GnssFix, ModemError> {
+        let nmea = self.get_location()?.nmea.unwrap_or_default();
+        Ok(GnssFix::from_nmea(&nmea)?)
     }
 
     pub fn is_ready(&self) -> bool {
@@ -220,41 +563,112 @@ impl IonModemCli {
     }
 
     pub fn waiting_for_ready(&mut self) -> bool {
+        self.check_modem_lost();
         if !self.ready && self.modem_preparing() {
             self.ready = true;
         }
         self.ready
     }
 
-    pub fn setup_modem_enable(&self, status: bool) -> Result<(), Box<dyn Error>> {
+    pub fn setup_modem_enable(&self, status: bool) -> Result<(), ModemError> {
         let interface = "org.freedesktop.ModemManager1.Modem";
         let method = "Enable";
-        let connection: Connection = Connection::new_system()?;
 
         // Prepare the D-Bus message to enable the modem
-        let msg = Message::new_method_call("org.freedesktop.ModemManager1", &self.modem, interface, method)?.append1(status);
+        let msg = self.modem_method(interface, method)?.append1(status);
 
         // Send the message and handle the response
-        let _ = connection.send_with_reply_and_block(msg, Duration::from_millis(2000))?;
+        let _ = self.call_modem_with_timeout(msg, ENABLE_TIMEOUT)?;
+
+        Ok(())
+    }
+
+    // Reboot the modem, its object goes away and comes back once it's probed again
+    pub fn reset(&self) -> Result<(), ModemError> {
+        let msg = self.modem_method("org.freedesktop.ModemManager1.Modem", "Reset")?;
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
 
+    // Back to factory settings, code is the carrier supplied service code
+    pub fn factory_reset(&self, code: &str) -> Result<(), ModemError> {
+        if code.is_empty() {
+            return Err(ModemError::InvalidArgument(
+                "empty factory reset code".to_owned(),
+            ));
+        }
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "FactoryReset")?
+            .append1(code);
+        let _ = self.call_modem(msg)?;
         Ok(())
     }
 
-    pub fn setup_location(&self, sources: u32, signal_location: bool) -> Result<(), Box<dyn Error>> {
+    pub fn power_state(&self) -> Result<PowerState, ModemError> {
+        let state: u32 = self.get_property("org.freedesktop.ModemManager1.Modem", "PowerState")?;
+        Ok(PowerState::from(state))
+    }
+
+    // ModemManager only accepts it while the modem is disabled
+    pub fn set_power_state(&self, state: PowerState) -> Result<(), ModemError> {
+        if state == PowerState::Unknown {
+            return Err(ModemError::InvalidArgument(
+                "can't set power state to unknown".to_owned(),
+            ));
+        }
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "SetPowerState")?
+            .append1(state as u32);
+        let _ = self.call_modem_with_timeout(msg, POWER_STATE_TIMEOUT)?;
+        Ok(())
+    }
+
+    pub fn setup_location(
+        &self,
+        sources: LocationSources,
+        signal_location: bool,
+    ) -> Result<(), ModemError> {
         let interface = "org.freedesktop.ModemManager1.Modem.Location";
         let method = "Setup";
-        let connection: Connection = Connection::new_system()?;
 
         // Prepare the D-Bus message to setup location
-        let msg = Message::new_method_call("org.freedesktop.ModemManager1", &self.modem, interface, method)?.append2(sources, signal_location);
-
-        // Append arguments to the method call
-        
+        let msg = self
+            .modem_method(interface, method)?
+            .append2(sources.bits(), signal_location);
 
         // Send the message and handle the response
-        let _ = connection.send_with_reply_and_block(msg, Duration::from_millis(2000))?;
+        let _ = self.call_modem(msg)?;
 
         Ok(())
     }
 
 }
+
+pub(crate) fn object_path(path: &str) -> Result<dbus::Path<'static>, ModemError> {
+    dbus::Path::new(path.to_owned()).map_err(ModemError::InvalidArgument)
+}
+
+pub(crate) fn prop_u64(props: &PropMap, key: &str) -> Option<u64> {
+    props.get(key).and_then(|value| value.0.as_u64())
+}
+
+// Nested a{sv} dicts come back as generic RefArgs, flatten them into a PropMap.
+// Their values are variants already, they're unwrapped so prop_cast() sees the value.
+pub(crate) fn prop_map(props: &PropMap, key: &str) -> PropMap {
+    let mut map: PropMap = HashMap::new();
+    if let Some(mut entries) = props.get(key).and_then(|value| value.0.as_iter()) {
+        while let (Some(name), Some(value)) = (entries.next(), entries.next()) {
+            if let Some(name) = name.as_str() {
+                let value = match value.arg_type() {
+                    ArgType::Variant => value
+                        .as_iter()
+                        .and_then(|mut inner| inner.next())
+                        .unwrap_or(value),
+                    _ => value,
+                };
+                map.insert(name.to_owned(), Variant(value.box_clone()));
+            }
+        }
+    }
+    map
+}
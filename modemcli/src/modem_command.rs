@@ -0,0 +1,389 @@
+use crate::modem_cli::IonModemCli;
+use crate::modem_error::ModemError;
+use regex::Regex;
+use std::fmt;
+use std::time::Duration;
+
+// Which raw AT commands at_command() lets through. A command must start with one of the
+// allowed prefixes (case insensitive) and match none of the deny patterns. The default
+// policy allows nothing.
+#[derive(Clone, Debug, Default)]
+pub struct AtPolicy {
+    allow: Vec<String>,
+    deny: Vec<Regex>,
+}
+
+impl AtPolicy {
+    pub fn new() -> Self {
+        AtPolicy::default()
+    }
+
+    // e.g. "AT+QGPSCFG" or "AT+QTEMP"
+    pub fn allow(mut self, prefix: &str) -> Self {
+        self.allow.push(prefix.trim().to_ascii_uppercase());
+        self
+    }
+
+    pub fn deny(mut self, pattern: &str) -> Result<Self, ModemError> {
+        let pattern = Regex::new(&format!("(?i){}", pattern)).map_err(|e| {
+            ModemError::InvalidArgument(format!("bad AT deny pattern '{}': {}", pattern, e))
+        })?;
+        self.deny.push(pattern);
+        Ok(self)
+    }
+
+    pub fn check(&self, command: &str) -> Result<(), ModemError> {
+        // "AT+QTEMP;+CFUN=0" would sneak a second command past the prefix check
+        if command.contains([';', '\r', '\n']) {
+            return Err(ModemError::InvalidArgument(format!(
+                "AT command '{}' chains several commands",
+                command.escape_debug()
+            )));
+        }
+        let upper = command.trim().to_ascii_uppercase();
+        if !self
+            .allow
+            .iter()
+            .any(|prefix| upper.starts_with(prefix.as_str()))
+        {
+            return Err(ModemError::InvalidArgument(format!(
+                "AT command '{}' not allowed",
+                command
+            )));
+        }
+        if let Some(pattern) = self.deny.iter().find(|pattern| pattern.is_match(command)) {
+            return Err(ModemError::InvalidArgument(format!(
+                "AT command '{}' denied by '{}'",
+                command, pattern
+            )));
+        }
+        Ok(())
+    }
+}
+
+// +CME ERROR codes (3GPP TS 27.007) of ModemManager's MobileEquipment error names
+const CME_ERRORS: &[(&str, u32)] = &[
+    ("PhoneFailure", 0),
+    ("NoConnection", 1),
+    ("LinkReserved", 2),
+    ("NotAllowed", 3),
+    ("NotSupported", 4),
+    ("PhSimPin", 5),
+    ("PhFsimPin", 6),
+    ("PhFsimPuk", 7),
+    ("SimNotInserted", 10),
+    ("SimPin", 11),
+    ("SimPuk", 12),
+    ("SimFailure", 13),
+    ("SimBusy", 14),
+    ("SimWrong", 15),
+    ("IncorrectPassword", 16),
+    ("SimPin2", 17),
+    ("SimPuk2", 18),
+    ("MemoryFull", 20),
+    ("InvalidIndex", 21),
+    ("NotFound", 22),
+    ("MemoryFailure", 23),
+    ("TextTooLong", 24),
+    ("InvalidChars", 25),
+    ("DialStringTooLong", 26),
+    ("DialStringInvalid", 27),
+    ("NoNetwork", 30),
+    ("NetworkTimeout", 31),
+    ("NetworkNotAllowed", 32),
+    ("NetworkPin", 40),
+    ("NetworkPuk", 41),
+    ("NetworkSubsetPin", 42),
+    ("NetworkSubsetPuk", 43),
+    ("ServicePin", 44),
+    ("ServicePuk", 45),
+    ("CorpPin", 46),
+    ("CorpPuk", 47),
+    ("Unknown", 100),
+];
+
+// +CMS ERROR codes (3GPP TS 27.005) of ModemManager's Message error names
+const CMS_ERRORS: &[(&str, u32)] = &[
+    ("MeFailure", 300),
+    ("SmsServiceReserved", 301),
+    ("NotAllowed", 302),
+    ("NotSupported", 303),
+    ("InvalidPduParameter", 304),
+    ("InvalidTextParameter", 305),
+    ("SimNotInserted", 310),
+    ("SimPin", 311),
+    ("PhSimPin", 312),
+    ("SimFailure", 313),
+    ("SimBusy", 314),
+    ("SimWrong", 315),
+    ("SimPuk", 316),
+    ("SimPin2", 317),
+    ("SimPuk2", 318),
+    ("MemoryFailure", 320),
+    ("InvalidIndex", 321),
+    ("MemoryFull", 322),
+    ("SmscAddressUnknown", 330),
+    ("NoNetwork", 331),
+    ("NetworkTimeout", 332),
+    ("NoCnmaAckExpected", 340),
+    ("Unknown", 500),
+];
+
+fn error_code(table: &[(&str, u32)], name: &str) -> Option<u32> {
+    table
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, code)| *code)
+}
+
+// Final result code of an AT command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AtResult {
+    Ok,
+    Error,
+    // +CME ERROR: <n>, mobile equipment error
+    CmeError(u32),
+    // +CMS ERROR: <n>, messaging error
+    CmsError(u32),
+    // Verbose +CME/+CMS ERROR: <text>, when the modem isn't in numeric mode
+    ErrorText(String),
+}
+
+impl AtResult {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        match line {
+            "OK" => return Some(AtResult::Ok),
+            "ERROR" => return Some(AtResult::Error),
+            _ => {}
+        }
+        let (code, cme) = match (
+            line.strip_prefix("+CME ERROR:"),
+            line.strip_prefix("+CMS ERROR:"),
+        ) {
+            (Some(code), _) => (code.trim(), true),
+            (_, Some(code)) => (code.trim(), false),
+            _ => return None,
+        };
+        Some(match code.parse() {
+            Ok(code) if cme => AtResult::CmeError(code),
+            Ok(code) => AtResult::CmsError(code),
+            Err(_) => AtResult::ErrorText(code.to_owned()),
+        })
+    }
+
+    // ModemManager turns the modem's error result into a D-Bus error, name is the short
+    // error name (see ModemError::mm_error). Core.Unauthorized means the passthrough is off
+    // (no --debug), the modem never saw the command then.
+    fn from_mm_error(name: &str, message: &str) -> Option<Self> {
+        if let Some(error) = name.strip_prefix("MobileEquipment.") {
+            return Some(
+                error_code(CME_ERRORS, error)
+                    .map_or_else(|| AtResult::ErrorText(error.to_owned()), AtResult::CmeError),
+            );
+        }
+        if let Some(error) = name
+            .strip_prefix("Message.")
+            .or_else(|| name.strip_prefix("MessageError."))
+        {
+            return Some(
+                error_code(CMS_ERRORS, error)
+                    .map_or_else(|| AtResult::ErrorText(error.to_owned()), AtResult::CmsError),
+            );
+        }
+        match name.strip_prefix("Core.") {
+            None | Some("Unauthorized") => None,
+            Some(_) if message.is_empty() => Some(AtResult::Error),
+            Some(_) => Some(AtResult::ErrorText(message.to_owned())),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        *self == AtResult::Ok
+    }
+}
+
+impl fmt::Display for AtResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AtResult::Ok => f.write_str("OK"),
+            AtResult::Error => f.write_str("ERROR"),
+            AtResult::CmeError(code) => write!(f, "+CME ERROR: {}", code),
+            AtResult::CmsError(code) => write!(f, "+CMS ERROR: {}", code),
+            AtResult::ErrorText(text) => write!(f, "ERROR: {}", text),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtResponse {
+    // Non-empty response lines, without the echo and the final result code
+    pub lines: Vec<String>,
+    pub result: AtResult,
+}
+
+impl AtResponse {
+    // ModemManager strips the final OK from successful replies, no result code means OK
+    pub fn parse(command: &str, response: &str) -> Self {
+        let mut lines: Vec<String> = response
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.eq_ignore_ascii_case(command.trim()))
+            .map(str::to_owned)
+            .collect();
+        let result = match lines.last().and_then(|line| AtResult::parse(line)) {
+            Some(result) => {
+                lines.pop();
+                result
+            }
+            None => AtResult::Ok,
+        };
+        AtResponse { lines, result }
+    }
+}
+
+impl IonModemCli {
+    // Raw AT passthrough, only available while ModemManager runs with --debug.
+    // The command is checked against the client's AtPolicy first.
+    pub fn at_command(&self, command: &str, timeout: Duration) -> Result<AtResponse, ModemError> {
+        self.at_policy().check(command)?;
+        let seconds = timeout.as_secs().max(1) as u32;
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "Command")?
+            .append2(command.trim(), seconds);
+        // Leave ModemManager time to report its own timeout
+        let reply = match self.call_modem_with_timeout(msg, timeout + Duration::from_secs(1)) {
+            Ok(reply) => reply,
+            Err(e) => {
+                let result = match (&e, e.mm_error()) {
+                    (ModemError::ModemManager { message, .. }, Some(name)) => {
+                        AtResult::from_mm_error(name, message)
+                    }
+                    _ => None,
+                };
+                return result
+                    .map(|result| AtResponse {
+                        lines: Vec::new(),
+                        result,
+                    })
+                    .ok_or(e);
+            }
+        };
+        let response: String = reply.read1()?;
+        Ok(AtResponse::parse(command, &response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mm_error(name: &str, message: &str) -> ModemError {
+        ModemError::from(dbus::Error::new_custom(name, message))
+    }
+
+    fn at_result(e: &ModemError) -> Option<AtResult> {
+        match e {
+            ModemError::ModemManager { message, .. } => {
+                AtResult::from_mm_error(e.mm_error()?, message)
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn response_without_result_code_is_ok() {
+        let response = AtResponse::parse("AT+QTEMP", "+QTEMP: 32,35,33\r\n");
+        assert_eq!(response.lines, vec!["+QTEMP: 32,35,33"]);
+        assert_eq!(response.result, AtResult::Ok);
+    }
+
+    #[test]
+    fn response_drops_echo_and_result_code() {
+        let response = AtResponse::parse("AT+CSQ", "AT+CSQ\r\n\r\n+CSQ: 21,99\r\n\r\nOK\r\n");
+        assert_eq!(response.lines, vec!["+CSQ: 21,99"]);
+        assert_eq!(response.result, AtResult::Ok);
+    }
+
+    #[test]
+    fn response_error_codes() {
+        assert_eq!(
+            AtResponse::parse("AT+CPIN?", "ERROR").result,
+            AtResult::Error
+        );
+        assert_eq!(
+            AtResponse::parse("AT+CPIN?", "+CME ERROR: 10").result,
+            AtResult::CmeError(10)
+        );
+        assert_eq!(
+            AtResponse::parse("AT+CMGR=1", "+CMS ERROR: 321").result,
+            AtResult::CmsError(321)
+        );
+        assert_eq!(
+            AtResponse::parse("AT+CPIN?", "+CME ERROR: SIM not inserted").result,
+            AtResult::ErrorText("SIM not inserted".to_owned())
+        );
+    }
+
+    #[test]
+    fn response_keeps_intermediate_lines() {
+        let response = AtResponse::parse(
+            "AT+QGPSCFG=\"gnssconfig\"",
+            "+QGPSCFG: \"gnssconfig\",1\n+QGPSCFG: \"x\",0\n+CME ERROR: 3",
+        );
+        assert_eq!(response.lines.len(), 2);
+        assert_eq!(response.result, AtResult::CmeError(3));
+    }
+
+    #[test]
+    fn mobile_equipment_errors_map_to_cme() {
+        let e = mm_error(
+            "org.freedesktop.ModemManager1.Error.MobileEquipment.SimNotInserted",
+            "SIM not inserted",
+        );
+        assert_eq!(at_result(&e), Some(AtResult::CmeError(10)));
+        let e = mm_error(
+            "org.freedesktop.ModemManager1.Error.MobileEquipment.GprsUnknown",
+            "",
+        );
+        assert_eq!(
+            at_result(&e),
+            Some(AtResult::ErrorText("GprsUnknown".to_owned()))
+        );
+    }
+
+    #[test]
+    fn message_errors_map_to_cms() {
+        let e = mm_error(
+            "org.freedesktop.ModemManager1.Error.Message.InvalidIndex",
+            "",
+        );
+        assert_eq!(at_result(&e), Some(AtResult::CmsError(321)));
+        let e = mm_error(
+            "org.freedesktop.ModemManager1.Error.MessageError.MemoryFull",
+            "",
+        );
+        assert_eq!(at_result(&e), Some(AtResult::CmsError(322)));
+    }
+
+    #[test]
+    fn core_errors_map_to_error() {
+        let e = mm_error("org.freedesktop.ModemManager1.Error.Core.Failed", "");
+        assert_eq!(at_result(&e), Some(AtResult::Error));
+        let e = mm_error(
+            "org.freedesktop.ModemManager1.Error.Core.Failed",
+            "Serial command timed out",
+        );
+        assert_eq!(
+            at_result(&e),
+            Some(AtResult::ErrorText("Serial command timed out".to_owned()))
+        );
+        let e = mm_error(
+            "org.freedesktop.ModemManager1.Error.Core.Unauthorized",
+            "debug mode off",
+        );
+        assert_eq!(at_result(&e), None);
+        let e = mm_error("org.freedesktop.DBus.Error.NoReply", "");
+        assert_eq!(at_result(&e), None);
+    }
+}
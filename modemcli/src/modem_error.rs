@@ -0,0 +1,114 @@
+use crate::nmea::NmeaError;
+use std::error::Error;
+use std::fmt;
+
+// Prefix of every error name raised by ModemManager itself
+const MM_ERROR_PREFIX: &str = "org.freedesktop.ModemManager1.Error.";
+
+#[derive(Debug)]
+pub enum ModemError {
+    // Transport level failure: bus unreachable, timeout, unknown object...
+    Dbus(dbus::Error),
+    // Error raised by ModemManager, e.g. "org.freedesktop.ModemManager1.Error.Core.WrongState"
+    ModemManager {
+        name: String,
+        message: String,
+    },
+    // No modem object is exported (yet) by ModemManager
+    NoModem,
+    // The modem has no SIM inserted or active
+    NoSim,
+    // The reply or property did not carry the expected D-Bus type
+    UnexpectedType {
+        item: String,
+        expected: &'static str,
+    },
+    // Caller supplied value rejected before reaching the bus
+    InvalidArgument(String),
+    // Operation that outlived its own deadline, e.g. a USSD session
+    Timeout(String),
+    // Location data that could not be decoded
+    Nmea(NmeaError),
+}
+
+impl ModemError {
+    pub fn unexpected_type(item: &str, expected: &'static str) -> Self {
+        ModemError::UnexpectedType {
+            item: item.to_owned(),
+            expected,
+        }
+    }
+
+    // Short ModemManager error name without the common prefix, e.g. "Core.WrongState"
+    pub fn mm_error(&self) -> Option<&str> {
+        match self {
+            ModemError::ModemManager { name, .. } => Some(name.trim_start_matches(MM_ERROR_PREFIX)),
+            _ => None,
+        }
+    }
+
+    pub fn is_transport(&self) -> bool {
+        matches!(self, ModemError::Dbus(_))
+    }
+}
+
+impl fmt::Display for ModemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModemError::Dbus(e) => write!(
+                f,
+                "D-Bus error {}: {}",
+                e.name().unwrap_or("unknown"),
+                e.message().unwrap_or("")
+            ),
+            ModemError::ModemManager { name, message } => {
+                write!(f, "ModemManager error {}: {}", name, message)
+            }
+            ModemError::NoModem => write!(f, "no modem available"),
+            ModemError::NoSim => write!(f, "no SIM available"),
+            ModemError::UnexpectedType { item, expected } => {
+                write!(f, "unexpected type for {}, expected {}", item, expected)
+            }
+            ModemError::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            ModemError::Timeout(what) => write!(f, "{} timed out", what),
+            ModemError::Nmea(e) => write!(f, "NMEA error: {}", e),
+        }
+    }
+}
+
+impl Error for ModemError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ModemError::Dbus(e) => Some(e),
+            ModemError::Nmea(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<dbus::Error> for ModemError {
+    fn from(e: dbus::Error) -> Self {
+        match e.name() {
+            Some(name) if name.starts_with(MM_ERROR_PREFIX) => ModemError::ModemManager {
+                name: name.to_owned(),
+                message: e.message().unwrap_or("").to_owned(),
+            },
+            _ => ModemError::Dbus(e),
+        }
+    }
+}
+
+impl From<dbus::arg::TypeMismatchError> for ModemError {
+    fn from(e: dbus::arg::TypeMismatchError) -> Self {
+        ModemError::UnexpectedType {
+            item: e.to_string(),
+            expected: "matching reply signature",
+        }
+    }
+}
+
+impl From<NmeaError> for ModemError {
+    fn from(e: NmeaError) -> Self {
+        ModemError::Nmea(e)
+    }
+}
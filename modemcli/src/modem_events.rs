@@ -0,0 +1,332 @@
+use crate::modem_3gpp::RegistrationState;
+use crate::modem_cli::IonModemCli;
+use crate::modem_error::ModemError;
+use crate::modem_location::Location;
+use crate::modem_signal::SignalQuality;
+use crate::modem_state::{ModemState, StateChangeReason};
+use crate::modem_time::TIME_INTERFACE;
+use crate::modem_voice::{CallState, CallStateReason, CALL_INTERFACE, VOICE_INTERFACE};
+use dbus::arg::RefArg;
+use dbus::blocking::stdintf::org_freedesktop_dbus::{
+    ObjectManagerInterfacesAdded, ObjectManagerInterfacesRemoved, PropertiesPropertiesChanged,
+};
+use dbus::blocking::Connection;
+use dbus::message::{MatchRule, Message};
+use dbus::strings::{BusName, Path};
+use log::{debug, trace, warn};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub(crate) type EventHandler = Arc<Mutex<dyn FnMut(ModemEvent) + Send>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModemEvent {
+    StateChanged {
+        old: ModemState,
+        new: ModemState,
+        reason: StateChangeReason,
+    },
+    SignalQuality(SignalQuality),
+    // Location property, only sent when location signalling is enabled
+    Location(Location),
+    Registration(RegistrationState),
+    // A modem object appeared on / vanished from ModemManager, with its object path
+    ModemAdded(String),
+    ModemRemoved(String),
+    // New message object, received is false for messages created locally
+    SmsAdded {
+        path: String,
+        received: bool,
+    },
+    // Call object created, incoming or outgoing, and removed again
+    CallAdded(String),
+    CallDeleted(String),
+    CallStateChanged {
+        path: String,
+        old: CallState,
+        new: CallState,
+        reason: CallStateReason,
+    },
+    // ISO 8601 time received from the network, see IonModemCli::network_time_from()
+    NetworkTime(String),
+}
+
+const MODEM_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem";
+
+fn dispatch(handler: &EventHandler, event: ModemEvent) {
+    trace!("Modem event: {:?}", event);
+    match handler.lock() {
+        Ok(mut callback) => (*callback)(event),
+        Err(_) => warn!("Modem event handler poisoned, dropping {:?}", event),
+    }
+}
+
+fn signal_quality_from(value: &dyn RefArg) -> Option<SignalQuality> {
+    let mut fields = value.as_iter()?;
+    let percent = fields.next()?.as_u64()?;
+    let recent = fields.next()?.as_u64()?;
+    Some(SignalQuality {
+        percent: percent as u32,
+        recent: recent != 0,
+    })
+}
+
+fn properties_to_events(changed: &PropertiesPropertiesChanged) -> Vec<ModemEvent> {
+    let mut events = Vec::new();
+    for (prop, value) in changed.changed_properties.iter() {
+        let event = match (changed.interface_name.as_str(), prop.as_str()) {
+            (MODEM_INTERFACE, "SignalQuality") => {
+                signal_quality_from(&value.0).map(ModemEvent::SignalQuality)
+            }
+            ("org.freedesktop.ModemManager1.Modem.Location", "Location") => {
+                Some(ModemEvent::Location(Location::from_refarg(&value.0)))
+            }
+            ("org.freedesktop.ModemManager1.Modem.Modem3gpp", "RegistrationState") => value
+                .0
+                .as_u64()
+                .map(|state| ModemEvent::Registration(RegistrationState::from(state as u32))),
+            _ => None,
+        };
+        if let Some(event) = event {
+            events.push(event);
+        }
+    }
+    events
+}
+
+fn is_modem_object(msg: &Message, modem_prefix: &str) -> bool {
+    match msg.path() {
+        Some(path) => path.starts_with(modem_prefix),
+        None => false,
+    }
+}
+
+impl IonModemCli {
+    // Deliver modem events to a callback. Events are only dispatched while
+    // process_events() is being called.
+    pub fn subscribe_with<F>(&self, callback: F) -> Result<(), ModemError>
+    where
+        F: FnMut(ModemEvent) + Send + 'static,
+    {
+        let handler: EventHandler = Arc::new(Mutex::new(callback));
+        let conn = self.connection()?;
+        self.add_event_matches(&conn, &handler)?;
+        self.handlers.borrow_mut().push(handler);
+        Ok(())
+    }
+
+    // Deliver modem events to a channel
+    pub fn subscribe(&self) -> Result<Receiver<ModemEvent>, ModemError> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribe_with(move |event| {
+            let _ = tx.send(event);
+        })?;
+        Ok(rx)
+    }
+
+    // Wait up to timeout for an incoming signal, then dispatch every signal already queued
+    // without waiting again. Returns true if at least one message was processed.
+    pub fn process_events(&self, timeout: Duration) -> Result<bool, ModemError> {
+        let conn = self.connection()?;
+        if !conn.process(timeout)? {
+            return Ok(false);
+        }
+        while conn.process(Duration::ZERO)? {}
+        Ok(true)
+    }
+
+    // Called for every subscriber when a (new) connection is opened
+    pub(crate) fn add_event_matches(
+        &self,
+        conn: &Connection,
+        handler: &EventHandler,
+    ) -> Result<(), ModemError> {
+        let sender = BusName::new(self.destination.clone()).map_err(ModemError::InvalidArgument)?;
+        let root = Path::new(self.object.clone()).map_err(ModemError::InvalidArgument)?;
+        let modem_prefix = format!("{}/Modem/", self.object);
+
+        let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+            .with_sender(sender.clone())
+            .with_namespaced_path(root.clone());
+        let props_handler = Arc::clone(handler);
+        let props_prefix = modem_prefix.clone();
+        conn.add_match(
+            rule,
+            move |changed: PropertiesPropertiesChanged, _: &Connection, msg: &Message| {
+                if is_modem_object(msg, &props_prefix) {
+                    for event in properties_to_events(&changed) {
+                        dispatch(&props_handler, event);
+                    }
+                }
+                true
+            },
+        )?;
+
+        let rule = MatchRule::new_signal(MODEM_INTERFACE, "StateChanged")
+            .with_sender(sender.clone())
+            .with_namespaced_path(root.clone());
+        let state_handler = Arc::clone(handler);
+        conn.add_match(
+            rule,
+            move |(old, new, reason): (i32, i32, u32), _: &Connection, _: &Message| {
+                dispatch(
+                    &state_handler,
+                    ModemEvent::StateChanged {
+                        old: ModemState::from(old),
+                        new: ModemState::from(new),
+                        reason: StateChangeReason::from(reason),
+                    },
+                );
+                true
+            },
+        )?;
+
+        let rule = MatchRule::new_signal("org.freedesktop.ModemManager1.Modem.Messaging", "Added")
+            .with_sender(sender.clone())
+            .with_namespaced_path(root.clone());
+        let sms_handler = Arc::clone(handler);
+        conn.add_match(
+            rule,
+            move |(sms, received): (Path<'static>, bool), _: &Connection, _: &Message| {
+                dispatch(
+                    &sms_handler,
+                    ModemEvent::SmsAdded {
+                        path: sms.to_string(),
+                        received,
+                    },
+                );
+                true
+            },
+        )?;
+
+        let rule = MatchRule::new_signal(VOICE_INTERFACE, "CallAdded")
+            .with_sender(sender.clone())
+            .with_namespaced_path(root.clone());
+        let call_added_handler = Arc::clone(handler);
+        conn.add_match(
+            rule,
+            move |(call,): (Path<'static>,), _: &Connection, _: &Message| {
+                dispatch(&call_added_handler, ModemEvent::CallAdded(call.to_string()));
+                true
+            },
+        )?;
+
+        let rule = MatchRule::new_signal(VOICE_INTERFACE, "CallDeleted")
+            .with_sender(sender.clone())
+            .with_namespaced_path(root.clone());
+        let call_deleted_handler = Arc::clone(handler);
+        conn.add_match(
+            rule,
+            move |(call,): (Path<'static>,), _: &Connection, _: &Message| {
+                dispatch(
+                    &call_deleted_handler,
+                    ModemEvent::CallDeleted(call.to_string()),
+                );
+                true
+            },
+        )?;
+
+        let rule = MatchRule::new_signal(TIME_INTERFACE, "NetworkTimeChanged")
+            .with_sender(sender.clone())
+            .with_namespaced_path(root.clone());
+        let time_handler = Arc::clone(handler);
+        conn.add_match(
+            rule,
+            move |(time,): (String,), _: &Connection, _: &Message| {
+                dispatch(&time_handler, ModemEvent::NetworkTime(time));
+                true
+            },
+        )?;
+
+        // Sent by the call object itself, the call is identified by the message path
+        let rule = MatchRule::new_signal(CALL_INTERFACE, "StateChanged")
+            .with_sender(sender)
+            .with_namespaced_path(root);
+        let call_state_handler = Arc::clone(handler);
+        conn.add_match(
+            rule,
+            move |(old, new, reason): (i32, i32, u32), _: &Connection, msg: &Message| {
+                if let Some(path) = msg.path() {
+                    dispatch(
+                        &call_state_handler,
+                        ModemEvent::CallStateChanged {
+                            path: path.to_string(),
+                            old: CallState::from(old),
+                            new: CallState::from(new),
+                            reason: CallStateReason::from(reason as i32),
+                        },
+                    );
+                }
+                true
+            },
+        )?;
+
+        let added_handler = Arc::clone(handler);
+        conn.add_match(
+            self.object_manager_rule("InterfacesAdded")?,
+            move |added: ObjectManagerInterfacesAdded, _: &Connection, _: &Message| {
+                if added.interfaces.contains_key(MODEM_INTERFACE) {
+                    dispatch(
+                        &added_handler,
+                        ModemEvent::ModemAdded(added.object.to_string()),
+                    );
+                }
+                true
+            },
+        )?;
+
+        let removed_handler = Arc::clone(handler);
+        conn.add_match(
+            self.object_manager_rule("InterfacesRemoved")?,
+            move |removed: ObjectManagerInterfacesRemoved, _: &Connection, _: &Message| {
+                if removed
+                    .interfaces
+                    .iter()
+                    .any(|interface| interface == MODEM_INTERFACE)
+                {
+                    dispatch(
+                        &removed_handler,
+                        ModemEvent::ModemRemoved(removed.object.to_string()),
+                    );
+                }
+                true
+            },
+        )?;
+
+        debug!("Subscribed to modem signals from {}", self.destination);
+        Ok(())
+    }
+
+    fn object_manager_rule(&self, member: &'static str) -> Result<MatchRule<'static>, ModemError> {
+        let sender = BusName::new(self.destination.clone()).map_err(ModemError::InvalidArgument)?;
+        let root = Path::new(self.object.clone()).map_err(ModemError::InvalidArgument)?;
+        Ok(
+            MatchRule::new_signal("org.freedesktop.DBus.ObjectManager", member)
+                .with_sender(sender)
+                .with_path(root),
+        )
+    }
+
+    // Record removed modems so waiting_for_ready() can drop a stale path.
+    // Attached to every connection, whether or not anyone subscribed.
+    pub(crate) fn track_modem_removal(&self, conn: &Connection) -> Result<(), ModemError> {
+        let lost_modems = Arc::clone(&self.lost_modems);
+        conn.add_match(
+            self.object_manager_rule("InterfacesRemoved")?,
+            move |removed: ObjectManagerInterfacesRemoved, _: &Connection, _: &Message| {
+                if removed
+                    .interfaces
+                    .iter()
+                    .any(|interface| interface == MODEM_INTERFACE)
+                {
+                    if let Ok(mut lost) = lost_modems.lock() {
+                        lost.push(removed.object.to_string());
+                    }
+                }
+                true
+            },
+        )?;
+        Ok(())
+    }
+}
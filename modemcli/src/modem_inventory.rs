@@ -0,0 +1,125 @@
+use crate::modem_cli::{prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use bitflags::bitflags;
+use dbus::arg::{prop_cast, PropMap};
+use log::trace;
+use serde::Serialize;
+
+bitflags! {
+    // MMModemCapability
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Capabilities: u32 {
+        const POTS = 1 << 0;
+        const CDMA_EVDO = 1 << 1;
+        const GSM_UMTS = 1 << 2;
+        const LTE = 1 << 3;
+        const IRIDIUM = 1 << 5;
+        const NR5G = 1 << 6;
+        const TDS = 1 << 7;
+    }
+}
+
+impl Capabilities {
+    // e.g. "GSM_UMTS|LTE"
+    fn describe(self) -> String {
+        self.iter_names()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+// One entry of Modem.Firmware.List
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FirmwareImage {
+    pub unique_id: String,
+    // MMFirmwareImageType: 1 generic, 2 gobi
+    pub image_type: u32,
+    pub selected: bool,
+}
+
+// Identity and firmware of the modem, serializable for after-sales reports
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ModemInventory {
+    pub path: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub revision: String,
+    pub hardware_revision: String,
+    pub carrier_configuration: String,
+    pub carrier_configuration_revision: String,
+    pub equipment_identifier: String,
+    pub imei: String,
+    pub iccid: String,
+    pub imsi: String,
+    pub own_numbers: Vec<String>,
+    // One entry per capability combination the modem supports, e.g. "GSM_UMTS|LTE"
+    pub supported_capabilities: Vec<String>,
+    pub current_capabilities: String,
+    pub firmware: Vec<FirmwareImage>,
+}
+
+impl IonModemCli {
+    // Missing pieces (no SIM, no Firmware interface...) are left empty rather than failing
+    pub fn inventory(&self) -> Result<ModemInventory, ModemError> {
+        let path = self.modem_path()?.to_owned();
+        let modem = self.get_all_properties(&path, "org.freedesktop.ModemManager1.Modem")?;
+        let text = |props: &PropMap, name: &str| {
+            prop_cast::<String>(props, name)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let capabilities = |value: u32| Capabilities::from_bits_truncate(value).describe();
+
+        let imei = self
+            .get_property::<String>("org.freedesktop.ModemManager1.Modem.Modem3gpp", "Imei")
+            .unwrap_or_default();
+        let sim = self.sim_info().unwrap_or_default();
+
+        Ok(ModemInventory {
+            manufacturer: text(&modem, "Manufacturer"),
+            model: text(&modem, "Model"),
+            revision: text(&modem, "Revision"),
+            hardware_revision: text(&modem, "HardwareRevision"),
+            carrier_configuration: text(&modem, "CarrierConfiguration"),
+            carrier_configuration_revision: text(&modem, "CarrierConfigurationRevision"),
+            equipment_identifier: text(&modem, "EquipmentIdentifier"),
+            imei,
+            iccid: sim.sim_identifier,
+            imsi: sim.imsi,
+            own_numbers: prop_cast::<Vec<String>>(&modem, "OwnNumbers")
+                .cloned()
+                .unwrap_or_default(),
+            supported_capabilities: prop_cast::<Vec<u32>>(&modem, "SupportedCapabilities")
+                .map(|supported| supported.iter().map(|value| capabilities(*value)).collect())
+                .unwrap_or_default(),
+            current_capabilities: capabilities(
+                prop_u64(&modem, "CurrentCapabilities").unwrap_or(0) as u32,
+            ),
+            firmware: self.firmware_list().unwrap_or_else(|e| {
+                trace!("Can't list firmware: {}", e);
+                Vec::new()
+            }),
+            path,
+        })
+    }
+
+    pub fn firmware_list(&self) -> Result<Vec<FirmwareImage>, ModemError> {
+        let msg = self.modem_method("org.freedesktop.ModemManager1.Modem.Firmware", "List")?;
+        let reply = self.call_modem(msg)?;
+        let (selected, installed): (String, Vec<PropMap>) = reply.read2()?;
+        Ok(installed
+            .iter()
+            .map(|image| {
+                let unique_id = prop_cast::<String>(image, "unique-id")
+                    .cloned()
+                    .unwrap_or_default();
+                FirmwareImage {
+                    selected: unique_id == selected,
+                    image_type: prop_u64(image, "image-type").unwrap_or(0) as u32,
+                    unique_id,
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,412 @@
+use crate::modem_cli::{prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use crate::nmea::GnssFix;
+use bitflags::bitflags;
+use dbus::arg::{prop_cast, ArgType, RefArg};
+use log::debug;
+use std::time::Duration;
+
+const LOCATION_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Location";
+
+bitflags! {
+    // MMModemLocationSource
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct LocationSources: u32 {
+        const THREEGPP_LAC_CI = 1 << 0;
+        const GPS_RAW = 1 << 1;
+        const GPS_NMEA = 1 << 2;
+        const CDMA_BS = 1 << 3;
+        const GPS_UNMANAGED = 1 << 4;
+        const AGPS_MSA = 1 << 5;
+        const AGPS_MSB = 1 << 6;
+    }
+}
+
+bitflags! {
+    // MMModemLocationAssistanceDataType
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct AssistanceDataTypes: u32 {
+        // Qualcomm XTRA orbit predictions
+        const XTRA = 1 << 0;
+    }
+}
+
+// Everything Location.Setup and SetGpsRefreshRate control, applied and read back as a whole
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LocationConfig {
+    pub sources: LocationSources,
+    // Publish the location in the Location property, needed for ModemEvent::Location
+    pub signal_location: bool,
+    // Seconds between two GNSS updates, 0 for as soon as available
+    pub refresh_rate: u32,
+}
+
+// Serving cell from the 3GPP LAC/CI source
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CellLocation {
+    // Kept as strings, "01" and "1" are different MNCs
+    pub mcc: String,
+    pub mnc: String,
+    pub lac: u32,
+    pub ci: u32,
+    pub tac: u32,
+}
+
+impl CellLocation {
+    // "MCC,MNC,LAC,CI,TAC" with LAC, CI and TAC in hexadecimal
+    fn parse(value: &str) -> Option<Self> {
+        let fields: Vec<&str> = value.split(',').map(str::trim).collect();
+        if fields.len() < 4 {
+            return None;
+        }
+        let hex = |field: Option<&&str>| match field {
+            Some(field) if !field.is_empty() => u32::from_str_radix(field, 16).ok(),
+            _ => Some(0),
+        };
+        Some(CellLocation {
+            mcc: fields[0].to_owned(),
+            mnc: fields[1].to_owned(),
+            lac: hex(fields.get(2))?,
+            ci: hex(fields.get(3))?,
+            tac: hex(fields.get(4))?,
+        })
+    }
+}
+
+// GPS raw source, decoded by the modem
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GpsRaw {
+    pub utc_time: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CdmaBsLocation {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+// Every entry returned by Location.GetLocation, one per enabled source
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Location {
+    pub cell: Option<CellLocation>,
+    pub gps_raw: Option<GpsRaw>,
+    pub nmea: Option<String>,
+    pub cdma_bs: Option<CdmaBsLocation>,
+}
+
+// Location values are variants, look through them to the carried value
+fn unwrap_variant(value: &dyn RefArg) -> &dyn RefArg {
+    if value.arg_type() == ArgType::Variant {
+        if let Some(inner) = value.as_iter().and_then(|mut inner| inner.next()) {
+            return inner;
+        }
+    }
+    value
+}
+
+// Iterate the (key, value) pairs of an a{s*} dict
+fn dict_entries(dict: &dyn RefArg) -> Vec<(String, &dyn RefArg)> {
+    let mut entries = Vec::new();
+    if let Some(mut items) = dict.as_iter() {
+        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+            if let Some(key) = key.as_str() {
+                entries.push((key.to_owned(), value));
+            }
+        }
+    }
+    entries
+}
+
+impl Location {
+    pub fn is_empty(&self) -> bool {
+        self.cell.is_none()
+            && self.gps_raw.is_none()
+            && self.nmea.is_none()
+            && self.cdma_bs.is_none()
+    }
+
+    // GNSS fix decoded from the NMEA source, if any
+    pub fn fix(&self) -> Option<GnssFix> {
+        self.nmea
+            .as_deref()
+            .and_then(|nmea| GnssFix::from_nmea(nmea).ok())
+    }
+
+    pub(crate) fn add_source(&mut self, source: u32, value: &dyn RefArg) {
+        let value = unwrap_variant(value);
+        match LocationSources::from_bits_truncate(source) {
+            LocationSources::THREEGPP_LAC_CI => {
+                self.cell = value.as_str().and_then(CellLocation::parse)
+            }
+            LocationSources::GPS_NMEA => self.nmea = value.as_str().map(|nmea| nmea.to_owned()),
+            LocationSources::GPS_RAW => {
+                let mut raw = GpsRaw::default();
+                for (key, value) in dict_entries(value) {
+                    match key.as_str() {
+                        "utc-time" => raw.utc_time = value.as_str().map(|time| time.to_owned()),
+                        "latitude" => raw.latitude = value.as_f64(),
+                        "longitude" => raw.longitude = value.as_f64(),
+                        "altitude" => raw.altitude = value.as_f64(),
+                        _ => {}
+                    }
+                }
+                self.gps_raw = Some(raw);
+            }
+            LocationSources::CDMA_BS => {
+                let mut bs = CdmaBsLocation::default();
+                for (key, value) in dict_entries(value) {
+                    match key.as_str() {
+                        "latitude" => bs.latitude = value.as_f64(),
+                        "longitude" => bs.longitude = value.as_f64(),
+                        _ => {}
+                    }
+                }
+                self.cdma_bs = Some(bs);
+            }
+            _ => {}
+        }
+    }
+
+    // Decode an a{uv} location dict, e.g. the Location property
+    pub(crate) fn from_refarg(location: &dyn RefArg) -> Self {
+        let mut decoded = Location::default();
+        if let Some(mut entries) = location.as_iter() {
+            while let (Some(source), Some(value)) = (entries.next(), entries.next()) {
+                if let Some(source) = source.as_u64() {
+                    decoded.add_source(source as u32, value);
+                }
+            }
+        }
+        decoded
+    }
+}
+
+impl IonModemCli {
+    pub fn location_sources(&self) -> Result<LocationSources, ModemError> {
+        let sources: u32 = self.get_property(LOCATION_INTERFACE, "Enabled")?;
+        Ok(LocationSources::from_bits_truncate(sources))
+    }
+
+    // Sources the modem can provide at all
+    pub fn location_capabilities(&self) -> Result<LocationSources, ModemError> {
+        let capabilities: u32 = self.get_property(LOCATION_INTERFACE, "Capabilities")?;
+        Ok(LocationSources::from_bits_truncate(capabilities))
+    }
+
+    pub fn signals_location(&self) -> Result<bool, ModemError> {
+        self.get_property(LOCATION_INTERFACE, "SignalsLocation")
+    }
+
+    pub fn location_config(&self) -> Result<LocationConfig, ModemError> {
+        let props = self.get_all_properties(self.modem_path()?, LOCATION_INTERFACE)?;
+        Ok(LocationConfig {
+            sources: LocationSources::from_bits_truncate(
+                prop_u64(&props, "Enabled").unwrap_or(0) as u32
+            ),
+            signal_location: prop_cast::<bool>(&props, "SignalsLocation")
+                .copied()
+                .unwrap_or(false),
+            refresh_rate: prop_u64(&props, "GpsRefreshRate").unwrap_or(0) as u32,
+        })
+    }
+
+    // Sources the modem can't provide are refused up front. If Setup fails the previous
+    // refresh rate is put back, so the modem is left as it was. Returns the configuration
+    // read back from the modem, which may still differ from the requested one.
+    pub fn apply_location_config(
+        &self,
+        config: &LocationConfig,
+    ) -> Result<LocationConfig, ModemError> {
+        let unsupported = config.sources - self.location_capabilities()?;
+        if !unsupported.is_empty() {
+            return Err(ModemError::InvalidArgument(format!(
+                "location sources {:?} not supported",
+                unsupported
+            )));
+        }
+
+        let previous = self.location_config()?;
+        let rate_changed = previous.refresh_rate != config.refresh_rate;
+        if rate_changed {
+            self.set_gps_refresh_rate(config.refresh_rate)?;
+        }
+        if let Err(e) = self.setup_location(config.sources, config.signal_location) {
+            if rate_changed {
+                if let Err(e) = self.set_gps_refresh_rate(previous.refresh_rate) {
+                    debug!("Can't restore GPS refresh rate: {}", e);
+                }
+            }
+            return Err(e);
+        }
+
+        let applied = self.location_config()?;
+        if applied != *config {
+            debug!(
+                "Location config {:?} requested, modem has {:?}",
+                config, applied
+            );
+        }
+        Ok(applied)
+    }
+
+    // "host:port" or a URL, empty when none is set
+    pub fn supl_server(&self) -> Result<String, ModemError> {
+        self.get_property(LOCATION_INTERFACE, "SuplServer")
+    }
+
+    pub fn set_supl_server(&self, server: &str) -> Result<(), ModemError> {
+        let msg = self
+            .modem_method(LOCATION_INTERFACE, "SetSuplServer")?
+            .append1(server);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn supported_assistance_data(&self) -> Result<AssistanceDataTypes, ModemError> {
+        let types: u32 = self.get_property(LOCATION_INTERFACE, "SupportedAssistanceData")?;
+        Ok(AssistanceDataTypes::from_bits_truncate(types))
+    }
+
+    // Where the assistance data accepted by the modem can be downloaded from
+    pub fn assistance_data_servers(&self) -> Result<Vec<String>, ModemError> {
+        self.get_property(LOCATION_INTERFACE, "AssistanceDataServers")
+    }
+
+    // Push a downloaded assistance file (e.g. xtra2.bin) to the GNSS engine.
+    // The modem has to accept the data as is, it's checked by the firmware only.
+    pub fn inject_assistance_data(&self, data: &[u8], timeout: Duration) -> Result<(), ModemError> {
+        if data.is_empty() {
+            return Err(ModemError::InvalidArgument(
+                "empty assistance data".to_owned(),
+            ));
+        }
+        let msg = self
+            .modem_method(LOCATION_INTERFACE, "InjectAssistanceData")?
+            .append1(data);
+        let _ = self.call_modem_with_timeout(msg, timeout)?;
+        Ok(())
+    }
+
+    // Seconds between two GNSS location updates, 0 updates as soon as new data is available
+    pub fn gps_refresh_rate(&self) -> Result<u32, ModemError> {
+        self.get_property(LOCATION_INTERFACE, "GpsRefreshRate")
+    }
+
+    pub fn set_gps_refresh_rate(&self, rate: u32) -> Result<(), ModemError> {
+        let msg = self
+            .modem_method(LOCATION_INTERFACE, "SetGpsRefreshRate")?
+            .append1(rate);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::{PropMap, Variant};
+    use dbus::message::Message;
+    use std::collections::HashMap;
+
+    const NMEA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+
+    fn variant<T: RefArg + 'static>(value: T) -> Variant<Box<dyn RefArg>> {
+        Variant(Box::new(value))
+    }
+
+    // a{uv} Location property as it comes off the bus
+    fn location_reply(sources: HashMap<u32, Variant<Box<dyn RefArg>>>) -> Location {
+        let msg = Message::new_signal(
+            "/org/freedesktop/ModemManager1/Modem/0",
+            "org.test",
+            "Reply",
+        )
+        .unwrap()
+        .append1(sources);
+        let location = msg.iter_init().get_refarg().unwrap();
+        Location::from_refarg(&*location)
+    }
+
+    #[test]
+    fn cell_location_is_hexadecimal() {
+        assert_eq!(
+            CellLocation::parse("208,01,1A2B,0C3D4E5,7F"),
+            Some(CellLocation {
+                mcc: "208".to_owned(),
+                mnc: "01".to_owned(),
+                lac: 0x1a2b,
+                ci: 0xc3d4e5,
+                tac: 0x7f
+            })
+        );
+    }
+
+    #[test]
+    fn cell_location_without_tac() {
+        let cell = CellLocation::parse("310, 260, FFFE, 10").unwrap();
+        assert_eq!(cell.mnc, "260");
+        assert_eq!((cell.lac, cell.ci, cell.tac), (0xfffe, 0x10, 0));
+        assert_eq!(CellLocation::parse("310,260,,10,").unwrap().lac, 0);
+    }
+
+    #[test]
+    fn broken_cell_location() {
+        assert_eq!(CellLocation::parse("208,01,1A2B"), None);
+        assert_eq!(CellLocation::parse("208,01,XYZ,10"), None);
+        assert_eq!(CellLocation::parse(""), None);
+    }
+
+    #[test]
+    fn every_source_is_decoded() {
+        let mut raw: PropMap = HashMap::new();
+        raw.insert("utc-time".to_owned(), variant("123519".to_owned()));
+        raw.insert("latitude".to_owned(), variant(48.1173));
+        raw.insert("longitude".to_owned(), variant(11.5167));
+        raw.insert("altitude".to_owned(), variant(545.4));
+        let mut sources = HashMap::new();
+        sources.insert(
+            LocationSources::THREEGPP_LAC_CI.bits(),
+            variant("208,01,1A2B,0C3D4E5,7F".to_owned()),
+        );
+        sources.insert(LocationSources::GPS_RAW.bits(), variant(raw));
+        sources.insert(LocationSources::GPS_NMEA.bits(), variant(NMEA.to_owned()));
+
+        let location = location_reply(sources);
+        assert_eq!(location.cell.as_ref().map(|cell| cell.ci), Some(0xc3d4e5));
+        assert_eq!(
+            location.gps_raw,
+            Some(GpsRaw {
+                utc_time: Some("123519".to_owned()),
+                latitude: Some(48.1173),
+                longitude: Some(11.5167),
+                altitude: Some(545.4),
+            })
+        );
+        assert_eq!(location.nmea.as_deref(), Some(NMEA));
+        assert!(location.fix().is_some_and(|fix| fix.has_position()));
+        assert_eq!(location.cdma_bs, None);
+    }
+
+    #[test]
+    fn partial_gps_raw() {
+        let mut raw: PropMap = HashMap::new();
+        raw.insert("latitude".to_owned(), variant(-33.8688));
+        raw.insert("unknown".to_owned(), variant(1u32));
+        let mut sources = HashMap::new();
+        sources.insert(LocationSources::GPS_RAW.bits(), variant(raw));
+
+        let location = location_reply(sources);
+        let raw = location.gps_raw.clone().unwrap();
+        assert_eq!(raw.latitude, Some(-33.8688));
+        assert_eq!((raw.longitude, raw.utc_time), (None, None));
+        assert!(location.cell.is_none() && location.fix().is_none());
+    }
+
+    #[test]
+    fn empty_location() {
+        assert!(location_reply(HashMap::new()).is_empty());
+    }
+}
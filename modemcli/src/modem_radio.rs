@@ -0,0 +1,331 @@
+use crate::modem_cli::IonModemCli;
+use crate::modem_error::ModemError;
+use bitflags::bitflags;
+use std::fmt;
+
+bitflags! {
+    // MMModemAccessTechnology
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct AccessTechnologies: u32 {
+        const POTS = 1 << 0;
+        const GSM = 1 << 1;
+        const GSM_COMPACT = 1 << 2;
+        const GPRS = 1 << 3;
+        const EDGE = 1 << 4;
+        const UMTS = 1 << 5;
+        const HSDPA = 1 << 6;
+        const HSUPA = 1 << 7;
+        const HSPA = 1 << 8;
+        const HSPA_PLUS = 1 << 9;
+        const ONE_X_RTT = 1 << 10;
+        const EVDO0 = 1 << 11;
+        const EVDOA = 1 << 12;
+        const EVDOB = 1 << 13;
+        const LTE = 1 << 14;
+        const NR5G = 1 << 15;
+        const LTE_CAT_M = 1 << 16;
+        const LTE_NB_IOT = 1 << 17;
+    }
+}
+
+bitflags! {
+    // MMModemMode
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Modes: u32 {
+        const CS = 1 << 0;
+        const MODE_2G = 1 << 1;
+        const MODE_3G = 1 << 2;
+        const MODE_4G = 1 << 3;
+        const MODE_5G = 1 << 4;
+    }
+}
+
+// One (allowed, preferred) pair of SupportedModes / CurrentModes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ModeCombination {
+    pub allowed: Modes,
+    // Empty when the modem has no preference among the allowed modes
+    pub preferred: Modes,
+}
+
+impl ModeCombination {
+    pub fn new(allowed: Modes, preferred: Modes) -> Self {
+        ModeCombination { allowed, preferred }
+    }
+
+    // MM_MODEM_MODE_ANY is all bits set, keep unknown bits so it's sent back unchanged
+    fn from_raw((allowed, preferred): (u32, u32)) -> Self {
+        ModeCombination {
+            allowed: Modes::from_bits_retain(allowed),
+            preferred: Modes::from_bits_retain(preferred),
+        }
+    }
+}
+
+// MMModemBand. Numbered bands are grouped per radio access network with the 3GPP/3GPP2 band number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Band {
+    Unknown,
+    Egsm,
+    Dcs,
+    Pcs,
+    G850,
+    G450,
+    G480,
+    G750,
+    G380,
+    G410,
+    G710,
+    G810,
+    // UMTS band I, II...
+    Utran(u32),
+    // LTE band 1, 2...
+    Eutran(u32),
+    // CDMA band class 0, 1...
+    Cdma(u32),
+    // 5G NR band n1, n2...
+    Ngran(u32),
+    // Let the modem use every band it supports, only valid on its own
+    Any,
+}
+
+// UTRAN 1 to 9 were added out of order, later ones follow 200 + band
+const UTRAN_LOW: [(u32, u32); 9] = [
+    (1, 5),
+    (2, 12),
+    (3, 6),
+    (4, 7),
+    (5, 9),
+    (6, 8),
+    (7, 13),
+    (8, 10),
+    (9, 11),
+];
+
+impl Band {
+    pub fn bits(self) -> u32 {
+        match self {
+            Band::Unknown => 0,
+            Band::Egsm => 1,
+            Band::Dcs => 2,
+            Band::Pcs => 3,
+            Band::G850 => 4,
+            Band::G450 => 14,
+            Band::G480 => 15,
+            Band::G750 => 16,
+            Band::G380 => 17,
+            Band::G410 => 18,
+            Band::G710 => 19,
+            Band::G810 => 20,
+            Band::Utran(band) => UTRAN_LOW
+                .iter()
+                .find(|(number, _)| *number == band)
+                .map(|(_, value)| *value)
+                .unwrap_or(200 + band),
+            Band::Eutran(band) => 30 + band,
+            Band::Cdma(class) => 128 + class,
+            Band::Ngran(band) => 300 + band,
+            Band::Any => 256,
+        }
+    }
+}
+
+impl From<u32> for Band {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => Band::Egsm,
+            2 => Band::Dcs,
+            3 => Band::Pcs,
+            4 => Band::G850,
+            14 => Band::G450,
+            15 => Band::G480,
+            16 => Band::G750,
+            17 => Band::G380,
+            18 => Band::G410,
+            19 => Band::G710,
+            20 => Band::G810,
+            5..=13 => UTRAN_LOW
+                .iter()
+                .find(|(_, raw)| *raw == value)
+                .map(|(number, _)| Band::Utran(*number))
+                .unwrap_or(Band::Unknown),
+            31..=115 => Band::Eutran(value - 30),
+            128..=147 => Band::Cdma(value - 128),
+            210..=255 => Band::Utran(value - 200),
+            256 => Band::Any,
+            301..=599 => Band::Ngran(value - 300),
+            _ => Band::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Band::Unknown => f.write_str("unknown"),
+            Band::Egsm => f.write_str("egsm"),
+            Band::Dcs => f.write_str("dcs"),
+            Band::Pcs => f.write_str("pcs"),
+            Band::G850 => f.write_str("g850"),
+            Band::G450 => f.write_str("g450"),
+            Band::G480 => f.write_str("g480"),
+            Band::G750 => f.write_str("g750"),
+            Band::G380 => f.write_str("g380"),
+            Band::G410 => f.write_str("g410"),
+            Band::G710 => f.write_str("g710"),
+            Band::G810 => f.write_str("g810"),
+            Band::Utran(band) => write!(f, "utran-{}", band),
+            Band::Eutran(band) => write!(f, "eutran-{}", band),
+            Band::Cdma(class) => write!(f, "cdma-bc{}", class),
+            Band::Ngran(band) => write!(f, "ngran-{}", band),
+            Band::Any => f.write_str("any"),
+        }
+    }
+}
+
+impl IonModemCli {
+    // Technologies currently in use, e.g. LTE
+    pub fn access_technologies(&self) -> Result<AccessTechnologies, ModemError> {
+        let technologies: u32 =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "AccessTechnologies")?;
+        Ok(AccessTechnologies::from_bits_truncate(technologies))
+    }
+
+    pub fn supported_modes(&self) -> Result<Vec<ModeCombination>, ModemError> {
+        let modes: Vec<(u32, u32)> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "SupportedModes")?;
+        Ok(modes.into_iter().map(ModeCombination::from_raw).collect())
+    }
+
+    pub fn current_modes(&self) -> Result<ModeCombination, ModemError> {
+        let modes: (u32, u32) =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "CurrentModes")?;
+        Ok(ModeCombination::from_raw(modes))
+    }
+
+    // Only combinations listed by supported_modes() are accepted
+    pub fn set_current_modes(&self, modes: ModeCombination) -> Result<(), ModemError> {
+        if !self.supported_modes()?.contains(&modes) {
+            return Err(ModemError::InvalidArgument(format!(
+                "modes {:?} (preferred {:?}) not supported by the modem",
+                modes.allowed, modes.preferred
+            )));
+        }
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "SetCurrentModes")?
+            .append1((modes.allowed.bits(), modes.preferred.bits()));
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn supported_bands(&self) -> Result<Vec<Band>, ModemError> {
+        let bands: Vec<u32> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "SupportedBands")?;
+        Ok(bands.into_iter().map(Band::from).collect())
+    }
+
+    pub fn current_bands(&self) -> Result<Vec<Band>, ModemError> {
+        let bands: Vec<u32> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "CurrentBands")?;
+        Ok(bands.into_iter().map(Band::from).collect())
+    }
+
+    // Restrict the modem to the given bands, or pass [Band::Any] to lift the restriction
+    pub fn set_current_bands(&self, bands: &[Band]) -> Result<(), ModemError> {
+        if bands.is_empty() {
+            return Err(ModemError::InvalidArgument("empty band list".to_owned()));
+        }
+        if bands.contains(&Band::Any) {
+            if bands.len() > 1 {
+                return Err(ModemError::InvalidArgument(
+                    "band 'any' can't be combined with other bands".to_owned(),
+                ));
+            }
+        } else {
+            let supported = self.supported_bands()?;
+            if let Some(band) = bands.iter().find(|band| !supported.contains(band)) {
+                return Err(ModemError::InvalidArgument(format!(
+                    "band {} not supported by the modem",
+                    band
+                )));
+            }
+        }
+        let bands: Vec<u32> = bands.iter().map(|band| band.bits()).collect();
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "SetCurrentBands")?
+            .append1(bands);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utran_low_bands_are_out_of_order() {
+        assert_eq!(Band::Utran(1).bits(), 5);
+        assert_eq!(Band::Utran(2).bits(), 12);
+        assert_eq!(Band::Utran(5).bits(), 9);
+        assert_eq!(Band::Utran(9).bits(), 11);
+        assert_eq!(Band::from(5), Band::Utran(1));
+        assert_eq!(Band::from(12), Band::Utran(2));
+        assert_eq!(Band::from(13), Band::Utran(7));
+    }
+
+    #[test]
+    fn utran_high_bands_follow_200() {
+        assert_eq!(Band::Utran(10).bits(), 210);
+        assert_eq!(Band::Utran(32).bits(), 232);
+        assert_eq!(Band::from(219), Band::Utran(19));
+    }
+
+    #[test]
+    fn every_band_round_trips() {
+        let mut bands = vec![
+            Band::Egsm,
+            Band::Dcs,
+            Band::Pcs,
+            Band::G850,
+            Band::G450,
+            Band::G480,
+            Band::G750,
+            Band::G380,
+            Band::G410,
+            Band::G710,
+            Band::G810,
+            Band::Any,
+        ];
+        bands.extend((1..=32).map(Band::Utran));
+        bands.extend((1..=85).map(Band::Eutran));
+        bands.extend((0..=19).map(Band::Cdma));
+        bands.extend((1..=299).map(Band::Ngran));
+        for band in bands {
+            assert_eq!(Band::from(band.bits()), band, "{}", band);
+        }
+    }
+
+    #[test]
+    fn numbered_bands() {
+        assert_eq!(Band::Eutran(20).bits(), 50);
+        assert_eq!(Band::from(31), Band::Eutran(1));
+        assert_eq!(Band::Cdma(0).bits(), 128);
+        assert_eq!(Band::Ngran(78).bits(), 378);
+        assert_eq!(Band::from(256), Band::Any);
+    }
+
+    #[test]
+    fn unassigned_values_are_unknown() {
+        for value in [0, 21, 30, 116, 148, 200, 209, 257, 300, 600] {
+            assert_eq!(Band::from(value), Band::Unknown, "{}", value);
+        }
+    }
+
+    #[test]
+    fn any_mode_keeps_unknown_bits() {
+        let modes = ModeCombination::from_raw((0xFFFF_FFFF, 0));
+        assert_eq!(modes.allowed.bits(), 0xFFFF_FFFF);
+        assert!(modes.preferred.is_empty());
+    }
+}
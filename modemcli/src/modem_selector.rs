@@ -0,0 +1,159 @@
+use crate::modem_cli::IonModemCli;
+use crate::modem_error::ModemError;
+use dbus::arg::{prop_cast, PropMap};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// Identifiers of one modem object exported by ModemManager
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModemInfo {
+    pub path: String,
+    pub equipment_identifier: String,
+    pub imei: Option<String>,
+    pub device: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub plugin: String,
+}
+
+impl ModemInfo {
+    pub(crate) fn from_interfaces(
+        path: &str,
+        interfaces: &HashMap<String, PropMap>,
+    ) -> Option<Self> {
+        let modem = interfaces.get("org.freedesktop.ModemManager1.Modem")?;
+        let text = |props: &PropMap, name: &str| {
+            prop_cast::<String>(props, name)
+                .cloned()
+                .unwrap_or_default()
+        };
+        Some(ModemInfo {
+            path: path.to_owned(),
+            equipment_identifier: text(modem, "EquipmentIdentifier"),
+            imei: interfaces
+                .get("org.freedesktop.ModemManager1.Modem.Modem3gpp")
+                .and_then(|props| prop_cast::<String>(props, "Imei").cloned()),
+            device: text(modem, "Device"),
+            manufacturer: text(modem, "Manufacturer"),
+            model: text(modem, "Model"),
+            plugin: text(modem, "Plugin"),
+        })
+    }
+}
+
+// Rule used to pick one modem when several are present
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ModemSelector {
+    // Whichever modem ModemManager lists first
+    #[default]
+    First,
+    Imei(String),
+    EquipmentId(String),
+    // Sysfs device path, e.g. "/sys/devices/platform/soc/1c1b000.usb/usb2/2-1"
+    Device(String),
+    // D-Bus object path, e.g. "/org/freedesktop/ModemManager1/Modem/0"
+    ObjectPath(String),
+    Model(String),
+}
+
+impl ModemSelector {
+    pub fn matches(&self, modem: &ModemInfo) -> bool {
+        match self {
+            ModemSelector::First => true,
+            ModemSelector::Imei(imei) => {
+                modem.imei.as_ref() == Some(imei) || modem.equipment_identifier == *imei
+            }
+            ModemSelector::EquipmentId(id) => modem.equipment_identifier == *id,
+            ModemSelector::Device(device) => modem.device == *device,
+            ModemSelector::ObjectPath(path) => modem.path == *path,
+            ModemSelector::Model(model) => modem.model == *model,
+        }
+    }
+}
+
+impl IonModemCli {
+    // All modems currently exported by ModemManager, in the order ModemManager found them
+    pub fn list_modems(&self) -> Result<Vec<ModemInfo>, ModemError> {
+        let mut modems: Vec<ModemInfo> = self
+            .managed_objects()?
+            .iter()
+            .filter_map(|(path, interfaces)| ModemInfo::from_interfaces(path, interfaces))
+            .collect();
+        modems.sort_by(|a, b| compare_paths(&a.path, &b.path));
+        Ok(modems)
+    }
+}
+
+// ".../Modem/2" comes before ".../Modem/10"
+fn compare_paths(a: &str, b: &str) -> Ordering {
+    let index = |path: &str| {
+        path.rsplit('/')
+            .next()
+            .and_then(|index| index.parse::<u64>().ok())
+    };
+    index(a).cmp(&index(b)).then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modem(path: &str) -> ModemInfo {
+        ModemInfo {
+            path: path.to_owned(),
+            imei: Some("356938035643809".to_owned()),
+            equipment_identifier: "356938035643809".to_owned(),
+            device: "/sys/devices/platform/soc/1c1b000.usb/usb2/2-1".to_owned(),
+            model: "EG25".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn paths_sort_by_modem_index() {
+        let mut paths = vec![
+            "/org/freedesktop/ModemManager1/Modem/10",
+            "/org/freedesktop/ModemManager1/Modem/2",
+            "/org/freedesktop/ModemManager1/Modem/1",
+        ];
+        paths.sort_by(|a, b| compare_paths(a, b));
+        assert_eq!(
+            paths,
+            vec![
+                "/org/freedesktop/ModemManager1/Modem/1",
+                "/org/freedesktop/ModemManager1/Modem/2",
+                "/org/freedesktop/ModemManager1/Modem/10",
+            ]
+        );
+    }
+
+    #[test]
+    fn selectors() {
+        let modem = modem("/org/freedesktop/ModemManager1/Modem/3");
+        assert!(ModemSelector::First.matches(&modem));
+        assert!(ModemSelector::Imei("356938035643809".to_owned()).matches(&modem));
+        assert!(ModemSelector::Model("EG25".to_owned()).matches(&modem));
+        assert!(
+            ModemSelector::ObjectPath("/org/freedesktop/ModemManager1/Modem/3".to_owned())
+                .matches(&modem)
+        );
+        assert!(
+            !ModemSelector::ObjectPath("/org/freedesktop/ModemManager1/Modem/30".to_owned())
+                .matches(&modem)
+        );
+        assert!(!ModemSelector::Device(
+            "/sys/devices/platform/soc/1c1b000.usb/usb2/2-2".to_owned()
+        )
+        .matches(&modem));
+    }
+
+    #[test]
+    fn imei_falls_back_on_the_equipment_identifier() {
+        let modem = ModemInfo {
+            imei: None,
+            ..modem("/org/freedesktop/ModemManager1/Modem/0")
+        };
+        assert!(ModemSelector::Imei("356938035643809".to_owned()).matches(&modem));
+        assert!(!ModemSelector::Imei("356938035643810".to_owned()).matches(&modem));
+    }
+}
@@ -0,0 +1,136 @@
+use crate::modem_cli::{prop_map, prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use dbus::arg::PropMap;
+
+const SIGNAL_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Signal";
+
+// Overall quality from Modem.SignalQuality
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SignalQuality {
+    pub percent: u32,
+    // false when the value is cached rather than freshly read from the modem
+    pub recent: bool,
+}
+
+// Values are in dBm / dB, None when the modem did not report them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LteSignal {
+    pub rssi: Option<f64>,
+    pub rsrq: Option<f64>,
+    pub rsrp: Option<f64>,
+    pub snr: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NrSignal {
+    pub rsrq: Option<f64>,
+    pub rsrp: Option<f64>,
+    pub snr: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UmtsSignal {
+    pub rssi: Option<f64>,
+    pub rscp: Option<f64>,
+    pub ecio: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GsmSignal {
+    pub rssi: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CdmaSignal {
+    pub rssi: Option<f64>,
+    pub ecio: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvdoSignal {
+    pub rssi: Option<f64>,
+    pub ecio: Option<f64>,
+    pub sinr: Option<f64>,
+    pub io: Option<f64>,
+}
+
+// Everything Modem.Signal reports, one entry per access technology in use
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SignalReport {
+    pub quality: SignalQuality,
+    // Refresh period in seconds configured with setup_signal(), 0 when disabled
+    pub rate: u32,
+    pub lte: Option<LteSignal>,
+    pub nr5g: Option<NrSignal>,
+    pub umts: Option<UmtsSignal>,
+    pub gsm: Option<GsmSignal>,
+    pub cdma: Option<CdmaSignal>,
+    pub evdo: Option<EvdoSignal>,
+}
+
+fn value(props: &PropMap, key: &str) -> Option<f64> {
+    props.get(key).and_then(|value| value.0.as_f64())
+}
+
+impl IonModemCli {
+    pub fn get_signal_quality(&self) -> Result<SignalQuality, ModemError> {
+        let (percent, recent): (u32, bool) =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "SignalQuality")?;
+        Ok(SignalQuality { percent, recent })
+    }
+
+    // Ask the modem to refresh the Modem.Signal values every rate seconds (0 disables).
+    // Without it ModemManager reports stale or empty dicts.
+    pub fn setup_signal(&self, rate: u32) -> Result<(), ModemError> {
+        let msg = self.modem_method(SIGNAL_INTERFACE, "Setup")?.append1(rate);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn signal_report(&self) -> Result<SignalReport, ModemError> {
+        let props = self.get_all_properties(self.modem_path()?, SIGNAL_INTERFACE)?;
+        // Each technology is an a{sv} dict, empty when not in use
+        let dict = |key: &str| -> Option<PropMap> {
+            let map = prop_map(&props, key);
+            if map.is_empty() {
+                None
+            } else {
+                Some(map)
+            }
+        };
+
+        Ok(SignalReport {
+            quality: self.get_signal_quality()?,
+            rate: prop_u64(&props, "Rate").unwrap_or(0) as u32,
+            lte: dict("Lte").map(|lte| LteSignal {
+                rssi: value(&lte, "rssi"),
+                rsrq: value(&lte, "rsrq"),
+                rsrp: value(&lte, "rsrp"),
+                snr: value(&lte, "snr"),
+            }),
+            nr5g: dict("Nr5g").map(|nr| NrSignal {
+                rsrq: value(&nr, "rsrq"),
+                rsrp: value(&nr, "rsrp"),
+                snr: value(&nr, "snr"),
+            }),
+            umts: dict("Umts").map(|umts| UmtsSignal {
+                rssi: value(&umts, "rssi"),
+                rscp: value(&umts, "rscp"),
+                ecio: value(&umts, "ecio"),
+            }),
+            gsm: dict("Gsm").map(|gsm| GsmSignal {
+                rssi: value(&gsm, "rssi"),
+            }),
+            cdma: dict("Cdma").map(|cdma| CdmaSignal {
+                rssi: value(&cdma, "rssi"),
+                ecio: value(&cdma, "ecio"),
+            }),
+            evdo: dict("Evdo").map(|evdo| EvdoSignal {
+                rssi: value(&evdo, "rssi"),
+                ecio: value(&evdo, "ecio"),
+                sinr: value(&evdo, "sinr"),
+                io: value(&evdo, "io"),
+            }),
+        })
+    }
+}
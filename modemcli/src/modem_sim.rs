@@ -0,0 +1,347 @@
+use crate::modem_cli::{prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use dbus::arg::{prop_cast, PropMap};
+use std::collections::HashMap;
+use std::fmt;
+
+const SIM_INTERFACE: &str = "org.freedesktop.ModemManager1.Sim";
+
+// MMModemLock, the code the modem waits for before it can be enabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ModemLock {
+    Unknown = 0,
+    None = 1,
+    SimPin = 2,
+    SimPin2 = 3,
+    SimPuk = 4,
+    SimPuk2 = 5,
+    PhSpPin = 6,
+    PhSpPuk = 7,
+    PhNetPin = 8,
+    PhNetPuk = 9,
+    PhSimPin = 10,
+    PhCorpPin = 11,
+    PhCorpPuk = 12,
+    PhFsimPin = 13,
+    PhFsimPuk = 14,
+    PhNetsubPin = 15,
+    PhNetsubPuk = 16,
+}
+
+impl ModemLock {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModemLock::Unknown => "unknown",
+            ModemLock::None => "none",
+            ModemLock::SimPin => "sim-pin",
+            ModemLock::SimPin2 => "sim-pin2",
+            ModemLock::SimPuk => "sim-puk",
+            ModemLock::SimPuk2 => "sim-puk2",
+            ModemLock::PhSpPin => "ph-sp-pin",
+            ModemLock::PhSpPuk => "ph-sp-puk",
+            ModemLock::PhNetPin => "ph-net-pin",
+            ModemLock::PhNetPuk => "ph-net-puk",
+            ModemLock::PhSimPin => "ph-sim-pin",
+            ModemLock::PhCorpPin => "ph-corp-pin",
+            ModemLock::PhCorpPuk => "ph-corp-puk",
+            ModemLock::PhFsimPin => "ph-fsim-pin",
+            ModemLock::PhFsimPuk => "ph-fsim-puk",
+            ModemLock::PhNetsubPin => "ph-netsub-pin",
+            ModemLock::PhNetsubPuk => "ph-netsub-puk",
+        }
+    }
+}
+
+impl From<u32> for ModemLock {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => ModemLock::None,
+            2 => ModemLock::SimPin,
+            3 => ModemLock::SimPin2,
+            4 => ModemLock::SimPuk,
+            5 => ModemLock::SimPuk2,
+            6 => ModemLock::PhSpPin,
+            7 => ModemLock::PhSpPuk,
+            8 => ModemLock::PhNetPin,
+            9 => ModemLock::PhNetPuk,
+            10 => ModemLock::PhSimPin,
+            11 => ModemLock::PhCorpPin,
+            12 => ModemLock::PhCorpPuk,
+            13 => ModemLock::PhFsimPin,
+            14 => ModemLock::PhFsimPuk,
+            15 => ModemLock::PhNetsubPin,
+            16 => ModemLock::PhNetsubPuk,
+            _ => ModemLock::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ModemLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// MMSimType
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SimType {
+    #[default]
+    Unknown,
+    Physical,
+    Esim,
+}
+
+impl From<u32> for SimType {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => SimType::Physical,
+            2 => SimType::Esim,
+            _ => SimType::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimInfo {
+    pub path: String,
+    // false for a SIM sitting in a slot that isn't the primary one
+    pub active: bool,
+    pub sim_type: SimType,
+    // eUICC identifier, empty for a physical SIM
+    pub eid: String,
+    // ICCID
+    pub sim_identifier: String,
+    pub imsi: String,
+    // MCC/MNC of the home network, e.g. "20801"
+    pub operator_identifier: String,
+    pub operator_name: String,
+}
+
+impl SimInfo {
+    pub(crate) fn from_props(path: &str, props: &PropMap) -> Self {
+        let text = |name: &str| {
+            prop_cast::<String>(props, name)
+                .cloned()
+                .unwrap_or_default()
+        };
+        SimInfo {
+            path: path.to_owned(),
+            // Older ModemManager releases only expose the active SIM
+            active: prop_u64(props, "Active").unwrap_or(1) != 0,
+            sim_type: SimType::from(prop_u64(props, "SimType").unwrap_or(0) as u32),
+            eid: text("Eid"),
+            sim_identifier: text("SimIdentifier"),
+            imsi: text("Imsi"),
+            operator_identifier: text("OperatorIdentifier"),
+            operator_name: text("OperatorName"),
+        }
+    }
+}
+
+// One entry of Modem.SimSlots, slots are numbered from 1
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SimSlot {
+    pub slot: u32,
+    pub primary: bool,
+    // None when the slot is empty
+    pub sim: Option<SimInfo>,
+}
+
+// PIN and PUK codes are digits only, the modem rejects anything else
+fn check_code(kind: &str, code: &str) -> Result<(), ModemError> {
+    let valid_len = match kind {
+        "PUK" => code.len() == 8,
+        _ => (4..=8).contains(&code.len()),
+    };
+    if valid_len && code.chars().all(|c| c.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(ModemError::InvalidArgument(format!(
+            "malformed {} code",
+            kind
+        )))
+    }
+}
+
+impl IonModemCli {
+    pub fn unlock_required(&self) -> Result<ModemLock, ModemError> {
+        let lock: u32 =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "UnlockRequired")?;
+        Ok(ModemLock::from(lock))
+    }
+
+    // Attempts left for each lock, locks the modem doesn't report are missing
+    pub fn unlock_retries(&self) -> Result<HashMap<ModemLock, u32>, ModemError> {
+        let retries: HashMap<u32, u32> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "UnlockRetries")?;
+        Ok(retries
+            .into_iter()
+            .map(|(lock, count)| (ModemLock::from(lock), count))
+            .collect())
+    }
+
+    // Object path of the active SIM
+    pub fn sim_path(&self) -> Result<String, ModemError> {
+        let sim: dbus::Path<'static> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "Sim")?;
+        match &*sim {
+            "/" => Err(ModemError::NoSim),
+            path => Ok(path.to_owned()),
+        }
+    }
+
+    pub fn sim_info(&self) -> Result<SimInfo, ModemError> {
+        let sim = self.sim_path()?;
+        let props = self.get_all_properties(&sim, SIM_INTERFACE)?;
+        Ok(SimInfo::from_props(&sim, &props))
+    }
+
+    pub fn send_pin(&self, pin: &str) -> Result<(), ModemError> {
+        check_code("PIN", pin)?;
+        let msg = self
+            .object_method(&self.sim_path()?, SIM_INTERFACE, "SendPin")?
+            .append1(pin);
+        let _ = self.send_message(msg)?;
+        Ok(())
+    }
+
+    // Unblock the SIM and set a new PIN at the same time
+    pub fn send_puk(&self, puk: &str, pin: &str) -> Result<(), ModemError> {
+        check_code("PUK", puk)?;
+        check_code("PIN", pin)?;
+        let msg = self
+            .object_method(&self.sim_path()?, SIM_INTERFACE, "SendPuk")?
+            .append2(puk, pin);
+        let _ = self.send_message(msg)?;
+        Ok(())
+    }
+
+    // Turn the PIN request at power-up on or off
+    pub fn enable_pin(&self, pin: &str, enabled: bool) -> Result<(), ModemError> {
+        check_code("PIN", pin)?;
+        let msg = self
+            .object_method(&self.sim_path()?, SIM_INTERFACE, "EnablePin")?
+            .append2(pin, enabled);
+        let _ = self.send_message(msg)?;
+        Ok(())
+    }
+
+    pub fn change_pin(&self, old_pin: &str, new_pin: &str) -> Result<(), ModemError> {
+        check_code("PIN", old_pin)?;
+        check_code("PIN", new_pin)?;
+        let msg = self
+            .object_method(&self.sim_path()?, SIM_INTERFACE, "ChangePin")?
+            .append2(old_pin, new_pin);
+        let _ = self.send_message(msg)?;
+        Ok(())
+    }
+
+    // Every slot of a multi-SIM modem, empty when the modem has a single slot
+    pub fn sim_slots(&self) -> Result<Vec<SimSlot>, ModemError> {
+        let slots: Vec<dbus::Path<'static>> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "SimSlots")?;
+        let primary = self.primary_sim_slot()?;
+        let mut sim_slots = Vec::new();
+        for (index, path) in slots.iter().enumerate() {
+            let slot = index as u32 + 1;
+            let sim = match &**path {
+                "/" => None,
+                path => Some(SimInfo::from_props(
+                    path,
+                    &self.get_all_properties(path, SIM_INTERFACE)?,
+                )),
+            };
+            sim_slots.push(SimSlot {
+                slot,
+                primary: slot == primary,
+                sim,
+            });
+        }
+        Ok(sim_slots)
+    }
+
+    // 0 when the modem doesn't support multiple slots
+    pub fn primary_sim_slot(&self) -> Result<u32, ModemError> {
+        self.get_property("org.freedesktop.ModemManager1.Modem", "PrimarySimSlot")
+    }
+
+    // The modem is reprobed afterwards, it disappears and comes back under a new object path
+    pub fn set_primary_sim_slot(&self, slot: u32) -> Result<(), ModemError> {
+        let slots: Vec<dbus::Path<'static>> =
+            self.get_property("org.freedesktop.ModemManager1.Modem", "SimSlots")?;
+        if slot == 0 || slot as usize > slots.len() {
+            return Err(ModemError::InvalidArgument(format!(
+                "no SIM slot {} (modem has {})",
+                slot,
+                slots.len()
+            )));
+        }
+        let msg = self
+            .modem_method("org.freedesktop.ModemManager1.Modem", "SetPrimarySimSlot")?
+            .append1(slot);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    // Attempts left before the SIM falls back to PUK, None when unknown
+    pub fn pin_retries(&self) -> Result<Option<u32>, ModemError> {
+        Ok(self.unlock_retries()?.get(&ModemLock::SimPin).copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_is_four_to_eight_digits() {
+        for pin in ["1234", "123456", "12345678"] {
+            assert!(check_code("PIN", pin).is_ok(), "{}", pin);
+        }
+        for pin in [
+            "",
+            "123",
+            "123456789",
+            "12a4",
+            " 1234",
+            "1234\n",
+            "１２３４",
+        ] {
+            assert!(
+                matches!(check_code("PIN", pin), Err(ModemError::InvalidArgument(_))),
+                "{:?}",
+                pin
+            );
+        }
+    }
+
+    #[test]
+    fn puk_is_exactly_eight_digits() {
+        assert!(check_code("PUK", "12345678").is_ok());
+        for puk in ["1234", "1234567", "123456789", "1234567a"] {
+            assert!(
+                matches!(check_code("PUK", puk), Err(ModemError::InvalidArgument(_))),
+                "{:?}",
+                puk
+            );
+        }
+    }
+
+    #[test]
+    fn error_names_the_code() {
+        match check_code("PUK", "0000") {
+            Err(ModemError::InvalidArgument(message)) => assert_eq!(message, "malformed PUK code"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn locks_round_trip() {
+        for value in 0..=16 {
+            assert_eq!(ModemLock::from(value) as u32, value);
+        }
+        assert_eq!(ModemLock::from(17), ModemLock::Unknown);
+        assert_eq!(ModemLock::SimPuk2.to_string(), "sim-puk2");
+    }
+}
@@ -0,0 +1,304 @@
+use crate::modem_cli::{object_path, prop_u64, IonModemCli};
+use crate::modem_error::ModemError;
+use dbus::arg::{prop_cast, PropMap, Variant};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const MESSAGING_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Messaging";
+const SMS_INTERFACE: &str = "org.freedesktop.ModemManager1.Sms";
+
+// Sending waits for the network to acknowledge the message
+const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+
+// MMSmsState
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmsState {
+    #[default]
+    Unknown,
+    Stored,
+    // Multipart message with parts still missing
+    Receiving,
+    Received,
+    Sending,
+    Sent,
+}
+
+impl From<u32> for SmsState {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => SmsState::Stored,
+            2 => SmsState::Receiving,
+            3 => SmsState::Received,
+            4 => SmsState::Sending,
+            5 => SmsState::Sent,
+            _ => SmsState::Unknown,
+        }
+    }
+}
+
+// MMSmsPduType
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SmsPduType {
+    #[default]
+    Unknown,
+    Deliver,
+    Submit,
+    StatusReport,
+    CdmaDeliver,
+    CdmaSubmit,
+    CdmaCancellation,
+    CdmaDeliveryAcknowledgement,
+    CdmaUserAcknowledgement,
+    CdmaReadAcknowledgement,
+}
+
+impl From<u32> for SmsPduType {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => SmsPduType::Deliver,
+            2 => SmsPduType::Submit,
+            3 => SmsPduType::StatusReport,
+            32 => SmsPduType::CdmaDeliver,
+            33 => SmsPduType::CdmaSubmit,
+            34 => SmsPduType::CdmaCancellation,
+            35 => SmsPduType::CdmaDeliveryAcknowledgement,
+            36 => SmsPduType::CdmaUserAcknowledgement,
+            37 => SmsPduType::CdmaReadAcknowledgement,
+            _ => SmsPduType::Unknown,
+        }
+    }
+}
+
+// MMSmsStorage
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SmsStorage {
+    #[default]
+    Unknown = 0,
+    // SIM
+    Sm = 1,
+    // Modem memory
+    Me = 2,
+    // SIM and modem memory
+    Mt = 3,
+    // Status reports
+    Sr = 4,
+    // Cell broadcast
+    Bm = 5,
+    // Terminal adaptor
+    Ta = 6,
+}
+
+impl From<u32> for SmsStorage {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => SmsStorage::Sm,
+            2 => SmsStorage::Me,
+            3 => SmsStorage::Mt,
+            4 => SmsStorage::Sr,
+            5 => SmsStorage::Bm,
+            6 => SmsStorage::Ta,
+            _ => SmsStorage::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sms {
+    pub path: String,
+    pub number: String,
+    // Empty for binary messages, see data
+    pub text: String,
+    pub data: Vec<u8>,
+    pub smsc: String,
+    // ISO 8601 time the SMSC received the message, empty for messages we created
+    pub timestamp: String,
+    pub state: SmsState,
+    pub pdu_type: SmsPduType,
+    pub storage: SmsStorage,
+}
+
+impl Sms {
+    fn from_props(path: &str, props: &PropMap) -> Self {
+        let text = |name: &str| {
+            prop_cast::<String>(props, name)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let value = |name: &str| prop_u64(props, name).unwrap_or(0) as u32;
+        Sms {
+            path: path.to_owned(),
+            number: text("Number"),
+            text: text("Text"),
+            data: prop_cast::<Vec<u8>>(props, "Data")
+                .cloned()
+                .unwrap_or_default(),
+            smsc: text("SMSC"),
+            timestamp: text("Timestamp"),
+            state: SmsState::from(value("State")),
+            pdu_type: SmsPduType::from(value("PduType")),
+            storage: SmsStorage::from(value("Storage")),
+        }
+    }
+}
+
+// International "+<digits>" or national digits only
+pub(crate) fn check_number(number: &str) -> Result<(), ModemError> {
+    let digits = number.strip_prefix('+').unwrap_or(number);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ModemError::InvalidArgument(format!(
+            "'{}' is not a phone number",
+            number
+        )));
+    }
+    Ok(())
+}
+
+impl IonModemCli {
+    // Object paths of every message known to the modem
+    pub fn list_sms(&self) -> Result<Vec<String>, ModemError> {
+        let msg = self.modem_method(MESSAGING_INTERFACE, "List")?;
+        let reply = self.call_modem(msg)?;
+        let messages: Vec<dbus::Path<'static>> = reply.read1()?;
+        Ok(messages.iter().map(|path| path.to_string()).collect())
+    }
+
+    pub fn sms(&self, path: &str) -> Result<Sms, ModemError> {
+        let props = self.get_all_properties(path, SMS_INTERFACE)?;
+        Ok(Sms::from_props(path, &props))
+    }
+
+    // Create a text message, it's only sent with send_sms()
+    pub fn create_sms(&self, number: &str, text: &str) -> Result<String, ModemError> {
+        check_number(number)?;
+        if text.is_empty() {
+            return Err(ModemError::InvalidArgument("empty SMS text".to_owned()));
+        }
+        let mut props: PropMap = HashMap::new();
+        props.insert("number".to_owned(), Variant(Box::new(number.to_owned())));
+        props.insert("text".to_owned(), Variant(Box::new(text.to_owned())));
+        let msg = self
+            .modem_method(MESSAGING_INTERFACE, "Create")?
+            .append1(props);
+        let reply = self.call_modem(msg)?;
+        let sms: dbus::Path = reply.read1()?;
+        Ok(sms.to_string())
+    }
+
+    pub fn delete_sms(&self, path: &str) -> Result<(), ModemError> {
+        let msg = self
+            .modem_method(MESSAGING_INTERFACE, "Delete")?
+            .append1(object_path(path)?);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn send_sms(&self, path: &str) -> Result<(), ModemError> {
+        let msg = self.object_method(path, SMS_INTERFACE, "Send")?;
+        let _ = self.send_message_with_timeout(msg, SEND_TIMEOUT)?;
+        Ok(())
+    }
+
+    // Keep a copy of the message in the given storage
+    pub fn store_sms(&self, path: &str, storage: SmsStorage) -> Result<(), ModemError> {
+        let msg = self
+            .object_method(path, SMS_INTERFACE, "Store")?
+            .append1(storage as u32);
+        let _ = self.send_message(msg)?;
+        Ok(())
+    }
+
+    // Create and send a text message, returns its object path
+    pub fn send_text(&self, number: &str, text: &str) -> Result<String, ModemError> {
+        let sms = self.create_sms(number, text)?;
+        self.send_sms(&sms)?;
+        Ok(sms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn received_text() {
+        let mut props: PropMap = HashMap::new();
+        props.insert(
+            "Number".to_owned(),
+            Variant(Box::new("+33612345678".to_owned())),
+        );
+        props.insert("Text".to_owned(), Variant(Box::new("hello".to_owned())));
+        props.insert(
+            "SMSC".to_owned(),
+            Variant(Box::new("+33609001390".to_owned())),
+        );
+        props.insert(
+            "Timestamp".to_owned(),
+            Variant(Box::new("2024-03-23T12:35:19+01".to_owned())),
+        );
+        props.insert("State".to_owned(), Variant(Box::new(3u32)));
+        props.insert("PduType".to_owned(), Variant(Box::new(1u32)));
+        props.insert("Storage".to_owned(), Variant(Box::new(2u32)));
+        let sms = Sms::from_props("/org/freedesktop/ModemManager1/SMS/4", &props);
+        assert_eq!(sms.path, "/org/freedesktop/ModemManager1/SMS/4");
+        assert_eq!(sms.number, "+33612345678");
+        assert_eq!(sms.text, "hello");
+        assert!(sms.data.is_empty());
+        assert_eq!(sms.smsc, "+33609001390");
+        assert_eq!(sms.timestamp, "2024-03-23T12:35:19+01");
+        assert_eq!(sms.state, SmsState::Received);
+        assert_eq!(sms.pdu_type, SmsPduType::Deliver);
+        assert_eq!(sms.storage, SmsStorage::Me);
+    }
+
+    #[test]
+    fn binary_message() {
+        let mut props: PropMap = HashMap::new();
+        props.insert("Data".to_owned(), Variant(Box::new(vec![0x01u8, 0xFE])));
+        props.insert("PduType".to_owned(), Variant(Box::new(32u32)));
+        let sms = Sms::from_props("/org/freedesktop/ModemManager1/SMS/0", &props);
+        assert_eq!(sms.data, vec![0x01, 0xFE]);
+        assert!(sms.text.is_empty());
+        assert_eq!(sms.pdu_type, SmsPduType::CdmaDeliver);
+    }
+
+    #[test]
+    fn missing_and_unknown_values() {
+        let mut props: PropMap = HashMap::new();
+        props.insert("State".to_owned(), Variant(Box::new(9u32)));
+        props.insert("Storage".to_owned(), Variant(Box::new(7u32)));
+        let sms = Sms::from_props("/org/freedesktop/ModemManager1/SMS/1", &props);
+        assert_eq!(sms.number, "");
+        assert_eq!(sms.state, SmsState::Unknown);
+        assert_eq!(sms.pdu_type, SmsPduType::Unknown);
+        assert_eq!(sms.storage, SmsStorage::Unknown);
+    }
+
+    #[test]
+    fn storage_round_trips() {
+        for storage in [
+            SmsStorage::Sm,
+            SmsStorage::Me,
+            SmsStorage::Mt,
+            SmsStorage::Sr,
+            SmsStorage::Bm,
+            SmsStorage::Ta,
+        ] {
+            assert_eq!(SmsStorage::from(storage as u32), storage);
+        }
+    }
+
+    #[test]
+    fn phone_numbers() {
+        assert!(check_number("+33612345678").is_ok());
+        assert!(check_number("0612345678").is_ok());
+        assert!(check_number("112").is_ok());
+        for number in ["", "+", "06 12 34 56 78", "+33-6", "++336", "06123a"] {
+            assert!(
+                matches!(check_number(number), Err(ModemError::InvalidArgument(_))),
+                "{:?}",
+                number
+            );
+        }
+    }
+}
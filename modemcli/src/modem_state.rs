@@ -0,0 +1,185 @@
+use std::fmt;
+
+// MMModemState, ordered so that "state >= Enabled" means the modem is powered and usable
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(i32)]
+pub enum ModemState {
+    Failed = -1,
+    Unknown = 0,
+    Initializing = 1,
+    Locked = 2,
+    Disabled = 3,
+    Disabling = 4,
+    Enabling = 5,
+    Enabled = 6,
+    Searching = 7,
+    Registered = 8,
+    Disconnecting = 9,
+    Connecting = 10,
+    Connected = 11,
+}
+
+impl ModemState {
+    pub fn is_enabled(self) -> bool {
+        self >= ModemState::Enabled
+    }
+
+    pub fn is_registered(self) -> bool {
+        self >= ModemState::Registered
+    }
+
+    pub fn is_connected(self) -> bool {
+        self == ModemState::Connected
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModemState::Failed => "failed",
+            ModemState::Unknown => "unknown",
+            ModemState::Initializing => "initializing",
+            ModemState::Locked => "locked",
+            ModemState::Disabled => "disabled",
+            ModemState::Disabling => "disabling",
+            ModemState::Enabling => "enabling",
+            ModemState::Enabled => "enabled",
+            ModemState::Searching => "searching",
+            ModemState::Registered => "registered",
+            ModemState::Disconnecting => "disconnecting",
+            ModemState::Connecting => "connecting",
+            ModemState::Connected => "connected",
+        }
+    }
+}
+
+// Values added by newer ModemManager releases are reported as Unknown
+impl From<i32> for ModemState {
+    fn from(value: i32) -> Self {
+        match value {
+            -1 => ModemState::Failed,
+            1 => ModemState::Initializing,
+            2 => ModemState::Locked,
+            3 => ModemState::Disabled,
+            4 => ModemState::Disabling,
+            5 => ModemState::Enabling,
+            6 => ModemState::Enabled,
+            7 => ModemState::Searching,
+            8 => ModemState::Registered,
+            9 => ModemState::Disconnecting,
+            10 => ModemState::Connecting,
+            11 => ModemState::Connected,
+            _ => ModemState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ModemState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// MMModemStateFailedReason, only meaningful while the state is Failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum StateFailedReason {
+    None = 0,
+    Unknown = 1,
+    SimMissing = 2,
+    SimError = 3,
+    UnknownCapabilities = 4,
+    EsimWithoutProfiles = 5,
+}
+
+impl From<u32> for StateFailedReason {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => StateFailedReason::None,
+            2 => StateFailedReason::SimMissing,
+            3 => StateFailedReason::SimError,
+            4 => StateFailedReason::UnknownCapabilities,
+            5 => StateFailedReason::EsimWithoutProfiles,
+            _ => StateFailedReason::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for StateFailedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            StateFailedReason::None => "none",
+            StateFailedReason::Unknown => "unknown",
+            StateFailedReason::SimMissing => "sim-missing",
+            StateFailedReason::SimError => "sim-error",
+            StateFailedReason::UnknownCapabilities => "unknown-capabilities",
+            StateFailedReason::EsimWithoutProfiles => "esim-without-profiles",
+        };
+        f.write_str(reason)
+    }
+}
+
+// MMModemStateChangeReason, carried by the Modem.StateChanged signal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum StateChangeReason {
+    Unknown = 0,
+    UserRequested = 1,
+    Suspend = 2,
+    Failure = 3,
+}
+
+impl From<u32> for StateChangeReason {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => StateChangeReason::UserRequested,
+            2 => StateChangeReason::Suspend,
+            3 => StateChangeReason::Failure,
+            _ => StateChangeReason::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for StateChangeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            StateChangeReason::Unknown => "unknown",
+            StateChangeReason::UserRequested => "user-requested",
+            StateChangeReason::Suspend => "suspend",
+            StateChangeReason::Failure => "failure",
+        };
+        f.write_str(reason)
+    }
+}
+
+// MMModemPowerState
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum PowerState {
+    Unknown = 0,
+    Off = 1,
+    // Radio off, the modem still answers
+    Low = 2,
+    On = 3,
+}
+
+impl From<u32> for PowerState {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => PowerState::Off,
+            2 => PowerState::Low,
+            3 => PowerState::On,
+            _ => PowerState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            PowerState::Unknown => "unknown",
+            PowerState::Off => "off",
+            PowerState::Low => "low",
+            PowerState::On => "on",
+        };
+        f.write_str(state)
+    }
+}
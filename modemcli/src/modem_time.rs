@@ -0,0 +1,77 @@
+use crate::modem_cli::{prop_map, IonModemCli};
+use crate::modem_error::ModemError;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use dbus::arg::PropMap;
+
+pub(crate) const TIME_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Time";
+
+// Modem.Time NetworkTimezone, fields the network didn't send are None
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetworkTimezone {
+    // Minutes from UTC, daylight saving included
+    pub offset: Option<i32>,
+    // Minutes of daylight saving in offset
+    pub dst_offset: Option<i32>,
+    // GPS to UTC leap seconds
+    pub leap_seconds: Option<i32>,
+}
+
+impl NetworkTimezone {
+    fn from_props(props: &PropMap) -> Self {
+        let value = |name: &str| {
+            props
+                .get(name)
+                .and_then(|value| value.0.as_i64())
+                .map(|value| value as i32)
+        };
+        NetworkTimezone {
+            offset: value("offset"),
+            dst_offset: value("dst-offset"),
+            leap_seconds: value("leap-seconds"),
+        }
+    }
+}
+
+impl IonModemCli {
+    // Time last received from the network (NITZ), in UTC
+    pub fn network_time(&self) -> Result<DateTime<Utc>, ModemError> {
+        let msg = self.modem_method(TIME_INTERFACE, "GetNetworkTime")?;
+        let reply = self.call_modem(msg)?;
+        let time: String = reply.read1()?;
+        self.network_time_from(&time)
+    }
+
+    // Decode an ISO 8601 network time, e.g. from GetNetworkTime or a NetworkTimeChanged
+    // signal. Times without a UTC offset are local, NetworkTimezone gives the offset then.
+    pub fn network_time_from(&self, time: &str) -> Result<DateTime<Utc>, ModemError> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+            return Ok(time.with_timezone(&Utc));
+        }
+        let invalid = || ModemError::InvalidArgument(format!("'{}' is not a network time", time));
+        let local =
+            NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f").map_err(|_| invalid())?;
+        let offset = self
+            .network_timezone()?
+            .offset
+            .and_then(|minutes| FixedOffset::east_opt(minutes * 60))
+            .ok_or_else(|| {
+                ModemError::InvalidArgument(format!(
+                    "no timezone known for network time '{}'",
+                    time
+                ))
+            })?;
+        let time = local
+            .and_local_timezone(offset)
+            .single()
+            .ok_or_else(invalid)?;
+        Ok(time.with_timezone(&Utc))
+    }
+
+    pub fn network_timezone(&self) -> Result<NetworkTimezone, ModemError> {
+        let props = self.get_all_properties(self.modem_path()?, TIME_INTERFACE)?;
+        Ok(NetworkTimezone::from_props(&prop_map(
+            &props,
+            "NetworkTimezone",
+        )))
+    }
+}
@@ -0,0 +1,151 @@
+use crate::modem_cli::IonModemCli;
+use crate::modem_error::ModemError;
+use log::{debug, warn};
+use std::time::{Duration, Instant};
+
+const USSD_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Modem3gpp.Ussd";
+
+// MMModem3gppUssdSessionState
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UssdState {
+    #[default]
+    Unknown,
+    Idle,
+    Active,
+    // The network is waiting for respond()
+    UserResponse,
+}
+
+impl From<u32> for UssdState {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => UssdState::Idle,
+            2 => UssdState::Active,
+            3 => UssdState::UserResponse,
+            _ => UssdState::Unknown,
+        }
+    }
+}
+
+// One USSD exchange started with ussd_initiate(). The session is cancelled when dropped
+// while the network still waits for an answer, and respond() fails once timeout is over.
+pub struct UssdSession<'a> {
+    modem_cli: &'a IonModemCli,
+    started: Instant,
+    timeout: Duration,
+    reply: String,
+    closed: bool,
+}
+
+impl UssdSession<'_> {
+    // Last text received from the network
+    pub fn reply(&self) -> &str {
+        &self.reply
+    }
+
+    pub fn state(&self) -> Result<UssdState, ModemError> {
+        let state: u32 = self.modem_cli.get_property(USSD_INTERFACE, "State")?;
+        Ok(UssdState::from(state))
+    }
+
+    // Network initiated message that doesn't expect an answer
+    pub fn network_notification(&self) -> Result<String, ModemError> {
+        self.modem_cli
+            .get_property(USSD_INTERFACE, "NetworkNotification")
+    }
+
+    // Network initiated message waiting for an answer
+    pub fn network_request(&self) -> Result<String, ModemError> {
+        self.modem_cli
+            .get_property(USSD_INTERFACE, "NetworkRequest")
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.started.elapsed() >= self.timeout
+    }
+
+    // Answer a menu sent by the network, returns its next reply
+    pub fn respond(&mut self, response: &str) -> Result<&str, ModemError> {
+        if self.is_expired() {
+            self.cancel()?;
+            return Err(ModemError::Timeout("USSD session".to_owned()));
+        }
+        let remaining = self.timeout.saturating_sub(self.started.elapsed());
+        let msg = self
+            .modem_cli
+            .modem_method(USSD_INTERFACE, "Respond")?
+            .append1(response);
+        let reply = self.modem_cli.call_modem_with_timeout(msg, remaining)?;
+        self.reply = reply.read1()?;
+        Ok(&self.reply)
+    }
+
+    pub fn cancel(&mut self) -> Result<(), ModemError> {
+        self.closed = true;
+        let msg = self.modem_cli.modem_method(USSD_INTERFACE, "Cancel")?;
+        let _ = self.modem_cli.call_modem(msg)?;
+        Ok(())
+    }
+}
+
+impl Drop for UssdSession<'_> {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        match self.state() {
+            Ok(UssdState::Active) | Ok(UssdState::UserResponse) => {
+                debug!("Cancelling unfinished USSD session");
+                if let Err(e) = self.cancel() {
+                    warn!("Can't cancel USSD session: {}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl IonModemCli {
+    // Send a USSD code, e.g. "*100#". The session gives up after timeout.
+    pub fn ussd_initiate(
+        &self,
+        command: &str,
+        timeout: Duration,
+    ) -> Result<UssdSession<'_>, ModemError> {
+        if command.is_empty()
+            || !command
+                .chars()
+                .all(|c| c.is_ascii_digit() || "*#+".contains(c))
+        {
+            return Err(ModemError::InvalidArgument(format!(
+                "'{}' is not a USSD code",
+                command
+            )));
+        }
+        let started = Instant::now();
+        let msg = self
+            .modem_method(USSD_INTERFACE, "Initiate")?
+            .append1(command);
+        let reply = match self.call_modem_with_timeout(msg, timeout) {
+            Ok(reply) => reply,
+            Err(ModemError::Dbus(e)) if e.name() == Some("org.freedesktop.DBus.Error.NoReply") => {
+                // Don't leave the network side hanging
+                let _ = self.call_modem(self.modem_method(USSD_INTERFACE, "Cancel")?);
+                return Err(ModemError::Timeout(format!("USSD {}", command)));
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(UssdSession {
+            modem_cli: self,
+            started,
+            timeout,
+            reply: reply.read1()?,
+            closed: false,
+        })
+    }
+
+    pub fn ussd_state(&self) -> Result<UssdState, ModemError> {
+        let state: u32 = self.get_property(USSD_INTERFACE, "State")?;
+        Ok(UssdState::from(state))
+    }
+}
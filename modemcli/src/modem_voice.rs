@@ -0,0 +1,298 @@
+use crate::modem_cli::{object_path, IonModemCli};
+use crate::modem_error::ModemError;
+use crate::modem_sms::check_number;
+use dbus::arg::{prop_cast, PropMap, Variant};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+pub(crate) const VOICE_INTERFACE: &str = "org.freedesktop.ModemManager1.Modem.Voice";
+pub(crate) const CALL_INTERFACE: &str = "org.freedesktop.ModemManager1.Call";
+
+// Dialing and answering wait for the network
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+// MMCallState, the values are also what the daemon reports on CAN
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CallState {
+    #[default]
+    Unknown = 0,
+    Dialing = 1,
+    RingingOut = 2,
+    RingingIn = 3,
+    Active = 4,
+    Held = 5,
+    Waiting = 6,
+    Terminated = 7,
+}
+
+impl CallState {
+    // Still holding the line, from dialing or ringing to a held call
+    pub fn is_ongoing(self) -> bool {
+        !matches!(self, CallState::Unknown | CallState::Terminated)
+    }
+}
+
+impl From<i32> for CallState {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => CallState::Dialing,
+            2 => CallState::RingingOut,
+            3 => CallState::RingingIn,
+            4 => CallState::Active,
+            5 => CallState::Held,
+            6 => CallState::Waiting,
+            7 => CallState::Terminated,
+            _ => CallState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for CallState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            CallState::Unknown => "unknown",
+            CallState::Dialing => "dialing",
+            CallState::RingingOut => "ringing-out",
+            CallState::RingingIn => "ringing-in",
+            CallState::Active => "active",
+            CallState::Held => "held",
+            CallState::Waiting => "waiting",
+            CallState::Terminated => "terminated",
+        };
+        f.write_str(state)
+    }
+}
+
+// MMCallStateReason
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CallStateReason {
+    #[default]
+    Unknown = 0,
+    OutgoingStarted = 1,
+    IncomingNew = 2,
+    Accepted = 3,
+    Terminated = 4,
+    RefusedOrBusy = 5,
+    Error = 6,
+    AudioSetupFailed = 7,
+    Transferred = 8,
+    Deflected = 9,
+}
+
+impl From<i32> for CallStateReason {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => CallStateReason::OutgoingStarted,
+            2 => CallStateReason::IncomingNew,
+            3 => CallStateReason::Accepted,
+            4 => CallStateReason::Terminated,
+            5 => CallStateReason::RefusedOrBusy,
+            6 => CallStateReason::Error,
+            7 => CallStateReason::AudioSetupFailed,
+            8 => CallStateReason::Transferred,
+            9 => CallStateReason::Deflected,
+            _ => CallStateReason::Unknown,
+        }
+    }
+}
+
+// MMCallDirection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CallDirection {
+    #[default]
+    Unknown = 0,
+    Incoming = 1,
+    Outgoing = 2,
+}
+
+impl From<i32> for CallDirection {
+    fn from(value: i32) -> Self {
+        match value {
+            1 => CallDirection::Incoming,
+            2 => CallDirection::Outgoing,
+            _ => CallDirection::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Call {
+    pub path: String,
+    pub number: String,
+    pub state: CallState,
+    pub state_reason: CallStateReason,
+    pub direction: CallDirection,
+}
+
+impl Call {
+    fn from_props(path: &str, props: &PropMap) -> Self {
+        let value = |name: &str| {
+            props
+                .get(name)
+                .and_then(|value| value.0.as_i64())
+                .unwrap_or(0) as i32
+        };
+        Call {
+            path: path.to_owned(),
+            number: prop_cast::<String>(props, "Number")
+                .cloned()
+                .unwrap_or_default(),
+            state: CallState::from(value("State")),
+            state_reason: CallStateReason::from(value("StateReason")),
+            direction: CallDirection::from(value("Direction")),
+        }
+    }
+}
+
+impl IonModemCli {
+    // Object paths of every call known to the modem, terminated ones included until deleted
+    pub fn list_calls(&self) -> Result<Vec<String>, ModemError> {
+        let msg = self.modem_method(VOICE_INTERFACE, "ListCalls")?;
+        let reply = self.call_modem(msg)?;
+        let calls: Vec<dbus::Path<'static>> = reply.read1()?;
+        Ok(calls.iter().map(|path| path.to_string()).collect())
+    }
+
+    pub fn call(&self, path: &str) -> Result<Call, ModemError> {
+        let props = self.get_all_properties(path, CALL_INTERFACE)?;
+        Ok(Call::from_props(path, &props))
+    }
+
+    // Calls that still hold the line
+    pub fn ongoing_calls(&self) -> Result<Vec<Call>, ModemError> {
+        let mut calls = Vec::new();
+        for path in self.list_calls()? {
+            let call = self.call(&path)?;
+            if call.state.is_ongoing() {
+                calls.push(call);
+            }
+        }
+        Ok(calls)
+    }
+
+    // Create an outgoing call, it's only dialed with start_call()
+    pub fn create_call(&self, number: &str) -> Result<String, ModemError> {
+        check_number(number)?;
+        let mut props: PropMap = HashMap::new();
+        props.insert("number".to_owned(), Variant(Box::new(number.to_owned())));
+        let msg = self
+            .modem_method(VOICE_INTERFACE, "CreateCall")?
+            .append1(props);
+        let reply = self.call_modem(msg)?;
+        let call: dbus::Path = reply.read1()?;
+        Ok(call.to_string())
+    }
+
+    pub fn delete_call(&self, path: &str) -> Result<(), ModemError> {
+        let msg = self
+            .modem_method(VOICE_INTERFACE, "DeleteCall")?
+            .append1(object_path(path)?);
+        let _ = self.call_modem(msg)?;
+        Ok(())
+    }
+
+    pub fn hangup_all(&self) -> Result<(), ModemError> {
+        let msg = self.modem_method(VOICE_INTERFACE, "HangupAll")?;
+        let _ = self.call_modem_with_timeout(msg, CALL_TIMEOUT)?;
+        Ok(())
+    }
+
+    pub fn start_call(&self, path: &str) -> Result<(), ModemError> {
+        let msg = self.object_method(path, CALL_INTERFACE, "Start")?;
+        let _ = self.send_message_with_timeout(msg, CALL_TIMEOUT)?;
+        Ok(())
+    }
+
+    // Answer an incoming call
+    pub fn accept_call(&self, path: &str) -> Result<(), ModemError> {
+        let msg = self.object_method(path, CALL_INTERFACE, "Accept")?;
+        let _ = self.send_message_with_timeout(msg, CALL_TIMEOUT)?;
+        Ok(())
+    }
+
+    pub fn hangup_call(&self, path: &str) -> Result<(), ModemError> {
+        let msg = self.object_method(path, CALL_INTERFACE, "Hangup")?;
+        let _ = self.send_message_with_timeout(msg, CALL_TIMEOUT)?;
+        Ok(())
+    }
+
+    // Tones are 0-9, A-D, * and #, sent one after the other on an active call
+    pub fn send_dtmf(&self, path: &str, tones: &str) -> Result<(), ModemError> {
+        if tones.is_empty()
+            || !tones
+                .chars()
+                .all(|c| c.is_ascii_digit() || "ABCD*#".contains(c))
+        {
+            return Err(ModemError::InvalidArgument(format!(
+                "'{}' is not a DTMF sequence",
+                tones
+            )));
+        }
+        let msg = self
+            .object_method(path, CALL_INTERFACE, "SendDtmf")?
+            .append1(tones);
+        let _ = self.send_message_with_timeout(msg, CALL_TIMEOUT)?;
+        Ok(())
+    }
+
+    // Create and dial a call, returns its object path
+    pub fn dial(&self, number: &str) -> Result<String, ModemError> {
+        let call = self.create_call(number)?;
+        if let Err(e) = self.start_call(&call) {
+            let _ = self.delete_call(&call);
+            return Err(e);
+        }
+        Ok(call)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incoming_call() {
+        let mut props: PropMap = HashMap::new();
+        props.insert(
+            "Number".to_owned(),
+            Variant(Box::new("+33612345678".to_owned())),
+        );
+        props.insert("State".to_owned(), Variant(Box::new(3i32)));
+        props.insert("StateReason".to_owned(), Variant(Box::new(2i32)));
+        props.insert("Direction".to_owned(), Variant(Box::new(1i32)));
+        let call = Call::from_props("/org/freedesktop/ModemManager1/Call/2", &props);
+        assert_eq!(call.path, "/org/freedesktop/ModemManager1/Call/2");
+        assert_eq!(call.number, "+33612345678");
+        assert_eq!(call.state, CallState::RingingIn);
+        assert_eq!(call.state_reason, CallStateReason::IncomingNew);
+        assert_eq!(call.direction, CallDirection::Incoming);
+        assert!(call.state.is_ongoing());
+    }
+
+    #[test]
+    fn withheld_number_and_unknown_values() {
+        let mut props: PropMap = HashMap::new();
+        props.insert("State".to_owned(), Variant(Box::new(8i32)));
+        props.insert("StateReason".to_owned(), Variant(Box::new(-1i32)));
+        let call = Call::from_props("/org/freedesktop/ModemManager1/Call/0", &props);
+        assert_eq!(call.number, "");
+        assert_eq!(call.state, CallState::Unknown);
+        assert_eq!(call.state_reason, CallStateReason::Unknown);
+        assert_eq!(call.direction, CallDirection::Unknown);
+        assert!(!call.state.is_ongoing());
+    }
+
+    #[test]
+    fn states_match_can_values() {
+        for value in 0..=7 {
+            assert_eq!(CallState::from(value) as i32, value);
+        }
+        assert!(!CallState::Terminated.is_ongoing());
+        assert!(CallState::Held.is_ongoing());
+        assert_eq!(CallState::RingingOut.to_string(), "ringing-out");
+    }
+}
@@ -0,0 +1,739 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use log::debug;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+// Knots to km/h
+const KNOTS_TO_KMH: f64 = 1.852;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NmeaError {
+    // No sentence at all in the input
+    Empty,
+    // Sentence without the trailing "*hh"
+    MissingChecksum(String),
+    BadChecksum {
+        sentence: String,
+        expected: u8,
+        found: u8,
+    },
+    // Sentence that does not follow the NMEA 0183 layout
+    Malformed {
+        sentence: String,
+        reason: String,
+    },
+    // Valid sentence of a type we don't decode, e.g. "GPTXT"
+    Unsupported(String),
+}
+
+impl fmt::Display for NmeaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NmeaError::Empty => write!(f, "no NMEA sentence"),
+            NmeaError::MissingChecksum(sentence) => write!(f, "missing checksum in '{}'", sentence),
+            NmeaError::BadChecksum {
+                sentence,
+                expected,
+                found,
+            } => write!(
+                f,
+                "bad checksum in '{}': computed {:02X}, sentence has {:02X}",
+                sentence, expected, found
+            ),
+            NmeaError::Malformed { sentence, reason } => {
+                write!(f, "malformed sentence '{}': {}", sentence, reason)
+            }
+            NmeaError::Unsupported(kind) => write!(f, "unsupported sentence type {}", kind),
+        }
+    }
+}
+
+impl Error for NmeaError {}
+
+// GGA fix quality indicator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FixQuality {
+    #[default]
+    Invalid,
+    Gps,
+    Dgps,
+    Pps,
+    Rtk,
+    FloatRtk,
+    Estimated,
+    Manual,
+    Simulation,
+}
+
+impl From<u8> for FixQuality {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => FixQuality::Gps,
+            2 => FixQuality::Dgps,
+            3 => FixQuality::Pps,
+            4 => FixQuality::Rtk,
+            5 => FixQuality::FloatRtk,
+            6 => FixQuality::Estimated,
+            7 => FixQuality::Manual,
+            8 => FixQuality::Simulation,
+            _ => FixQuality::Invalid,
+        }
+    }
+}
+
+// GSA fix type
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FixType {
+    #[default]
+    NoFix,
+    Fix2d,
+    Fix3d,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NmeaTime {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub millis: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NmeaDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NmeaSentence {
+    Gga {
+        time: Option<NmeaTime>,
+        latitude: Option<f64>,
+        longitude: Option<f64>,
+        quality: FixQuality,
+        satellites_used: Option<u32>,
+        hdop: Option<f64>,
+        // Meters above mean sea level
+        altitude: Option<f64>,
+    },
+    Rmc {
+        time: Option<NmeaTime>,
+        date: Option<NmeaDate>,
+        // false when the receiver flags the data as void ('V')
+        valid: bool,
+        latitude: Option<f64>,
+        longitude: Option<f64>,
+        // km/h
+        speed: Option<f64>,
+        // Degrees from true north
+        course: Option<f64>,
+    },
+    Gsa {
+        fix_type: FixType,
+        satellites: Vec<u32>,
+        pdop: Option<f64>,
+        hdop: Option<f64>,
+        vdop: Option<f64>,
+    },
+    Gsv {
+        // Talker of the constellation, e.g. "GP", "GL", "GA"
+        talker: String,
+        satellites_in_view: u32,
+    },
+    Vtg {
+        course: Option<f64>,
+        // km/h
+        speed: Option<f64>,
+    },
+}
+
+// Position solution merged from every sentence of one NMEA block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GnssFix {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+    pub speed: Option<f64>,
+    pub heading: Option<f64>,
+    pub quality: FixQuality,
+    pub fix_type: FixType,
+    pub hdop: Option<f64>,
+    pub pdop: Option<f64>,
+    pub vdop: Option<f64>,
+    pub satellites_in_view: u32,
+    pub satellites_used: u32,
+    pub date: Option<NmeaDate>,
+    pub time: Option<NmeaTime>,
+}
+
+impl GnssFix {
+    pub fn has_position(&self) -> bool {
+        self.quality != FixQuality::Invalid && self.latitude.is_some() && self.longitude.is_some()
+    }
+
+    // UTC time of the fix from the RMC date and time. Receivers report their own
+    // clock before the first fix, so it's only trusted along with a position.
+    pub fn utc(&self) -> Option<DateTime<Utc>> {
+        if !self.has_position() {
+            return None;
+        }
+        let (date, time) = (self.date?, self.time?);
+        let utc = NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)?
+            .and_hms_milli_opt(
+                time.hour as u32,
+                time.minute as u32,
+                time.second as u32,
+                time.millis as u32,
+            )?;
+        Some(utc.and_utc())
+    }
+
+    // Parse a multi-sentence NMEA block as returned by Location.GetLocation.
+    // Broken or unknown sentences are skipped; fails only if none could be used.
+    pub fn from_nmea(nmea: &str) -> Result<Self, NmeaError> {
+        let mut fix = GnssFix::default();
+        let mut first_error: Option<NmeaError> = None;
+        let mut parsed = 0;
+        let mut in_view: HashMap<String, u32> = HashMap::new();
+        let mut gga_seen = false;
+        let mut rmc_valid = false;
+        let mut gga_used: Option<u32> = None;
+        let mut gsa_used = 0;
+
+        for line in nmea.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let sentence = match parse_sentence(line) {
+                Ok(sentence) => sentence,
+                Err(NmeaError::Unsupported(_)) => continue,
+                Err(e) => {
+                    debug!("Skipping NMEA sentence: {}", e);
+                    first_error.get_or_insert(e);
+                    continue;
+                }
+            };
+            parsed += 1;
+
+            match sentence {
+                NmeaSentence::Gga {
+                    time,
+                    latitude,
+                    longitude,
+                    quality,
+                    satellites_used,
+                    hdop,
+                    altitude,
+                } => {
+                    fix.time = time.or(fix.time);
+                    fix.latitude = latitude.or(fix.latitude);
+                    fix.longitude = longitude.or(fix.longitude);
+                    fix.quality = quality;
+                    fix.hdop = hdop.or(fix.hdop);
+                    fix.altitude = altitude.or(fix.altitude);
+                    gga_used = satellites_used.or(gga_used);
+                    gga_seen = true;
+                }
+                NmeaSentence::Rmc {
+                    time,
+                    date,
+                    valid,
+                    latitude,
+                    longitude,
+                    speed,
+                    course,
+                } => {
+                    fix.time = time.or(fix.time);
+                    fix.date = date.or(fix.date);
+                    rmc_valid |= valid;
+                    if valid {
+                        fix.latitude = fix.latitude.or(latitude);
+                        fix.longitude = fix.longitude.or(longitude);
+                        fix.speed = speed.or(fix.speed);
+                        fix.heading = course.or(fix.heading);
+                    }
+                }
+                NmeaSentence::Gsa {
+                    fix_type,
+                    satellites,
+                    pdop,
+                    hdop,
+                    vdop,
+                } => {
+                    // One GSA per constellation on multi-GNSS receivers
+                    if fix_type != FixType::NoFix {
+                        fix.fix_type = fix_type;
+                    }
+                    gsa_used += satellites.len() as u32;
+                    fix.pdop = pdop.or(fix.pdop);
+                    fix.hdop = fix.hdop.or(hdop);
+                    fix.vdop = vdop.or(fix.vdop);
+                }
+                NmeaSentence::Gsv {
+                    talker,
+                    satellites_in_view,
+                } => {
+                    in_view.insert(talker, satellites_in_view);
+                }
+                NmeaSentence::Vtg { course, speed } => {
+                    fix.heading = fix.heading.or(course);
+                    fix.speed = fix.speed.or(speed);
+                }
+            }
+        }
+
+        if parsed == 0 {
+            return Err(first_error.unwrap_or(NmeaError::Empty));
+        }
+        // Without GGA, a valid RMC is the only hint that there is a fix
+        if !gga_seen && rmc_valid {
+            fix.quality = FixQuality::Gps;
+        }
+        fix.satellites_in_view = in_view.values().sum();
+        fix.satellites_used = gga_used.unwrap_or(gsa_used);
+        Ok(fix)
+    }
+}
+
+fn malformed(sentence: &str, reason: &str) -> NmeaError {
+    NmeaError::Malformed {
+        sentence: sentence.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+// Check "$<body>*hh" and return the body
+fn checked_body(sentence: &str) -> Result<&str, NmeaError> {
+    let content = sentence
+        .strip_prefix('$')
+        .or_else(|| sentence.strip_prefix('!'))
+        .ok_or_else(|| malformed(sentence, "missing '$' start"))?;
+    let (body, checksum) = content
+        .rsplit_once('*')
+        .ok_or_else(|| NmeaError::MissingChecksum(sentence.to_owned()))?;
+    let found = u8::from_str_radix(checksum, 16)
+        .map_err(|_| malformed(sentence, "checksum is not hexadecimal"))?;
+    let expected = body.bytes().fold(0u8, |sum, byte| sum ^ byte);
+    if expected != found {
+        return Err(NmeaError::BadChecksum {
+            sentence: sentence.to_owned(),
+            expected,
+            found,
+        });
+    }
+    Ok(body)
+}
+
+fn optional<T: std::str::FromStr>(
+    sentence: &str,
+    field: Option<&&str>,
+    name: &str,
+) -> Result<Option<T>, NmeaError> {
+    match field {
+        None | Some(&"") => Ok(None),
+        Some(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| malformed(sentence, &format!("invalid {} '{}'", name, value))),
+    }
+}
+
+fn parse_time(sentence: &str, field: Option<&&str>) -> Result<Option<NmeaTime>, NmeaError> {
+    let value = match field {
+        None | Some(&"") => return Ok(None),
+        Some(value) => *value,
+    };
+    let invalid = || malformed(sentence, &format!("invalid time '{}'", value));
+    if value.len() < 6 || !value.is_ascii() {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| value[range].parse::<u8>().map_err(|_| invalid());
+    let (hour, minute, second) = (number(0..2)?, number(2..4)?, number(4..6)?);
+    let millis = match value[6..].strip_prefix('.') {
+        Some(fraction) if !fraction.is_empty() => {
+            let fraction: f64 = format!("0.{}", fraction).parse().map_err(|_| invalid())?;
+            (fraction * 1000.0).round() as u16
+        }
+        _ => 0,
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return Err(invalid());
+    }
+    Ok(Some(NmeaTime {
+        hour,
+        minute,
+        second,
+        millis,
+    }))
+}
+
+fn parse_date(sentence: &str, field: Option<&&str>) -> Result<Option<NmeaDate>, NmeaError> {
+    let value = match field {
+        None | Some(&"") => return Ok(None),
+        Some(value) => *value,
+    };
+    let invalid = || malformed(sentence, &format!("invalid date '{}'", value));
+    if value.len() != 6 || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| value[range].parse::<u8>().map_err(|_| invalid());
+    let (day, month, year) = (number(0..2)?, number(2..4)?, number(4..6)?);
+    if day == 0 || day > 31 || month == 0 || month > 12 {
+        return Err(invalid());
+    }
+    Ok(Some(NmeaDate {
+        year: 2000 + year as u16,
+        month,
+        day,
+    }))
+}
+
+// "ddmm.mmmm" / "dddmm.mmmm" plus hemisphere into signed decimal degrees
+fn parse_coordinate(
+    sentence: &str,
+    value: Option<&&str>,
+    hemisphere: Option<&&str>,
+) -> Result<Option<f64>, NmeaError> {
+    let raw: f64 = match optional(sentence, value, "coordinate")? {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+    let degrees = (raw / 100.0).trunc();
+    let decimal = degrees + (raw - degrees * 100.0) / 60.0;
+    match hemisphere {
+        Some(&"N") | Some(&"E") => Ok(Some(decimal)),
+        Some(&"S") | Some(&"W") => Ok(Some(-decimal)),
+        _ => Err(malformed(sentence, "missing hemisphere")),
+    }
+}
+
+// Parse and validate a single sentence, e.g. "$GPGGA,...*47"
+pub fn parse_sentence(sentence: &str) -> Result<NmeaSentence, NmeaError> {
+    let body = checked_body(sentence)?;
+    let fields: Vec<&str> = body.split(',').collect();
+    let address = fields[0];
+    // Proprietary sentences ("$PQXFI", "$PMTK001") have their own address layout
+    if address.starts_with('P') {
+        return Err(NmeaError::Unsupported(address.to_owned()));
+    }
+    if address.len() != 5 || !address.is_ascii() {
+        return Err(malformed(sentence, "invalid address field"));
+    }
+    let (talker, kind) = address.split_at(2);
+    let field = |index: usize| fields.get(index);
+
+    match kind {
+        "GGA" => {
+            if fields.len() < 10 {
+                return Err(malformed(sentence, "too few fields"));
+            }
+            let quality: Option<u8> = optional(sentence, field(6), "fix quality")?;
+            Ok(NmeaSentence::Gga {
+                time: parse_time(sentence, field(1))?,
+                latitude: parse_coordinate(sentence, field(2), field(3))?,
+                longitude: parse_coordinate(sentence, field(4), field(5))?,
+                quality: FixQuality::from(quality.unwrap_or(0)),
+                satellites_used: optional(sentence, field(7), "satellite count")?,
+                hdop: optional(sentence, field(8), "HDOP")?,
+                altitude: optional(sentence, field(9), "altitude")?,
+            })
+        }
+        "RMC" => {
+            if fields.len() < 10 {
+                return Err(malformed(sentence, "too few fields"));
+            }
+            let speed: Option<f64> = optional(sentence, field(7), "speed")?;
+            Ok(NmeaSentence::Rmc {
+                time: parse_time(sentence, field(1))?,
+                date: parse_date(sentence, field(9))?,
+                valid: field(2) == Some(&"A"),
+                latitude: parse_coordinate(sentence, field(3), field(4))?,
+                longitude: parse_coordinate(sentence, field(5), field(6))?,
+                speed: speed.map(|knots| knots * KNOTS_TO_KMH),
+                course: optional(sentence, field(8), "course")?,
+            })
+        }
+        "GSA" => {
+            if fields.len() < 18 {
+                return Err(malformed(sentence, "too few fields"));
+            }
+            let mut satellites = Vec::new();
+            for index in 3..=14 {
+                if let Some(prn) = optional::<u32>(sentence, field(index), "satellite id")? {
+                    satellites.push(prn);
+                }
+            }
+            let fix_type = match field(2) {
+                Some(&"2") => FixType::Fix2d,
+                Some(&"3") => FixType::Fix3d,
+                _ => FixType::NoFix,
+            };
+            Ok(NmeaSentence::Gsa {
+                fix_type,
+                satellites,
+                pdop: optional(sentence, field(15), "PDOP")?,
+                hdop: optional(sentence, field(16), "HDOP")?,
+                vdop: optional(sentence, field(17), "VDOP")?,
+            })
+        }
+        "GSV" => {
+            if fields.len() < 4 {
+                return Err(malformed(sentence, "too few fields"));
+            }
+            Ok(NmeaSentence::Gsv {
+                talker: talker.to_owned(),
+                satellites_in_view: optional(sentence, field(3), "satellites in view")?
+                    .unwrap_or(0),
+            })
+        }
+        "VTG" => {
+            if fields.len() < 9 {
+                return Err(malformed(sentence, "too few fields"));
+            }
+            Ok(NmeaSentence::Vtg {
+                course: optional(sentence, field(1), "course")?,
+                speed: optional(sentence, field(7), "speed")?,
+            })
+        }
+        _ => Err(NmeaError::Unsupported(address.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GGA: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+    const RMC: &str = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230324,003.1,W*61";
+    const GSA: &str = "$GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39";
+    const GSV: &str = "$GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75";
+    const GLONASS_GSV: &str = "$GLGSV,1,1,03,65,40,083,46,66,17,308,41,74,07,344,39*5D";
+    const VTG: &str = "$GPVTG,054.7,T,034.4,M,005.5,N,010.2,K*48";
+
+    fn assert_close(value: Option<f64>, expected: f64) {
+        let value = value.expect("value missing");
+        assert!((value - expected).abs() < 1e-6, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn gga() {
+        match parse_sentence(GGA).unwrap() {
+            NmeaSentence::Gga {
+                time,
+                latitude,
+                longitude,
+                quality,
+                satellites_used,
+                hdop,
+                altitude,
+            } => {
+                assert_eq!(
+                    time,
+                    Some(NmeaTime {
+                        hour: 12,
+                        minute: 35,
+                        second: 19,
+                        millis: 0
+                    })
+                );
+                assert_close(latitude, 48.0 + 7.038 / 60.0);
+                assert_close(longitude, 11.0 + 31.0 / 60.0);
+                assert_eq!(quality, FixQuality::Gps);
+                assert_eq!(satellites_used, Some(8));
+                assert_close(hdop, 0.9);
+                assert_close(altitude, 545.4);
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rmc() {
+        match parse_sentence(RMC).unwrap() {
+            NmeaSentence::Rmc {
+                time,
+                date,
+                valid,
+                latitude,
+                speed,
+                course,
+                ..
+            } => {
+                assert_eq!(
+                    time,
+                    Some(NmeaTime {
+                        hour: 12,
+                        minute: 35,
+                        second: 19,
+                        millis: 0
+                    })
+                );
+                assert_eq!(
+                    date,
+                    Some(NmeaDate {
+                        year: 2024,
+                        month: 3,
+                        day: 23
+                    })
+                );
+                assert!(valid);
+                assert_close(latitude, 48.0 + 7.038 / 60.0);
+                assert_close(speed, 22.4 * KNOTS_TO_KMH);
+                assert_close(course, 84.4);
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn void_rmc() {
+        match parse_sentence("$GPRMC,123519,V,,,,,,,230324,,*38").unwrap() {
+            NmeaSentence::Rmc {
+                valid, latitude, ..
+            } => {
+                assert!(!valid);
+                assert_eq!(latitude, None);
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn gsa() {
+        match parse_sentence(GSA).unwrap() {
+            NmeaSentence::Gsa {
+                fix_type,
+                satellites,
+                pdop,
+                hdop,
+                vdop,
+            } => {
+                assert_eq!(fix_type, FixType::Fix3d);
+                assert_eq!(satellites, vec![4, 5, 9, 12, 24]);
+                assert_close(pdop, 2.5);
+                assert_close(hdop, 1.3);
+                assert_close(vdop, 2.1);
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn gsv() {
+        assert_eq!(
+            parse_sentence(GSV).unwrap(),
+            NmeaSentence::Gsv {
+                talker: "GP".to_owned(),
+                satellites_in_view: 8
+            }
+        );
+    }
+
+    #[test]
+    fn vtg() {
+        match parse_sentence(VTG).unwrap() {
+            NmeaSentence::Vtg { course, speed } => {
+                assert_close(course, 54.7);
+                assert_close(speed, 10.2);
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let sentence = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*48";
+        assert!(matches!(
+            parse_sentence(sentence),
+            Err(NmeaError::BadChecksum {
+                expected: 0x47,
+                found: 0x48,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn truncated_sentence() {
+        assert!(matches!(
+            parse_sentence("$GPGGA,123519,4807.038,N"),
+            Err(NmeaError::MissingChecksum(_))
+        ));
+        assert!(matches!(
+            parse_sentence("$GPGGA,123519,4807.038,N*27"),
+            Err(NmeaError::Malformed { .. })
+        ));
+    }
+
+    #[test]
+    fn unsupported_sentences() {
+        assert_eq!(
+            parse_sentence("$GPTXT,01,01,02,ANTSTATUS=OK*3B"),
+            Err(NmeaError::Unsupported("GPTXT".to_owned()))
+        );
+        assert_eq!(
+            parse_sentence("$PQXFI,123519.0,4807.038,N,01131.000,E,545.4,1.2,2.3,0.4*56"),
+            Err(NmeaError::Unsupported("PQXFI".to_owned()))
+        );
+        assert_eq!(
+            parse_sentence("$PMTK001,604,3*32"),
+            Err(NmeaError::Unsupported("PMTK001".to_owned()))
+        );
+    }
+
+    #[test]
+    fn merge_block() {
+        let block = [
+            GGA,
+            RMC,
+            GSA,
+            GSV,
+            GLONASS_GSV,
+            VTG,
+            "$PQXFI,123519.0,4807.038,N,01131.000,E,545.4,1.2,2.3,0.4*56",
+        ]
+        .join("\r\n");
+        let fix = GnssFix::from_nmea(&block).unwrap();
+        assert!(fix.has_position());
+        assert_close(fix.latitude, 48.0 + 7.038 / 60.0);
+        assert_close(fix.altitude, 545.4);
+        // RMC speed and course win over VTG
+        assert_close(fix.speed, 22.4 * KNOTS_TO_KMH);
+        assert_close(fix.heading, 84.4);
+        assert_eq!(fix.fix_type, FixType::Fix3d);
+        assert_eq!(fix.satellites_used, 8);
+        assert_eq!(fix.satellites_in_view, 11);
+        assert_eq!(fix.utc().unwrap().to_rfc3339(), "2024-03-23T12:35:19+00:00");
+    }
+
+    #[test]
+    fn utc_needs_a_position() {
+        let fix = GnssFix::from_nmea("$GPRMC,123519,V,,,,,,,230324,,*38").unwrap();
+        assert_eq!(
+            fix.date,
+            Some(NmeaDate {
+                year: 2024,
+                month: 3,
+                day: 23
+            })
+        );
+        assert_eq!(fix.utc(), None);
+    }
+
+    #[test]
+    fn merge_skips_broken_sentences() {
+        let block = format!("{}\n$GPGGA,123519,4807.038,N*27\n{}", RMC, VTG);
+        let fix = GnssFix::from_nmea(&block).unwrap();
+        // A valid RMC alone counts as a fix
+        assert_eq!(fix.quality, FixQuality::Gps);
+        assert_close(fix.longitude, 11.0 + 31.0 / 60.0);
+    }
+
+    #[test]
+    fn merge_without_usable_sentence() {
+        assert_eq!(GnssFix::from_nmea(""), Err(NmeaError::Empty));
+        assert!(matches!(
+            GnssFix::from_nmea("$GPGGA,123519,4807.038,N*28"),
+            Err(NmeaError::BadChecksum { .. })
+        ));
+    }
+}
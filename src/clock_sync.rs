@@ -0,0 +1,299 @@
+use log::{debug, info, warn};
+use std::error::Error;
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// A lower ranked source is used again once the better one has been quiet this long
+const SOURCE_HOLDOFF: Duration = Duration::from_secs(3600);
+// Magic of chrony's SOCK refclock samples, "SOCK"
+const CHRONY_SOCK_MAGIC: i32 = 0x534f434b;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeSource {
+    // UTC from the GNSS RMC sentences
+    Gnss,
+    // Network time from Modem.Time
+    Nitz,
+}
+
+impl TimeSource {
+    // How far a sample can be off by the time it's applied: GNSS fixes reach the loop a
+    // refresh period late at worst, NITZ has a one second resolution and the network delay.
+    // Smaller offsets are left alone instead of chasing latency.
+    fn uncertainty(self) -> Duration {
+        match self {
+            TimeSource::Gnss => Duration::from_secs(1),
+            TimeSource::Nitz => Duration::from_secs(3),
+        }
+    }
+}
+
+impl FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "gnss" | "gps" => Ok(TimeSource::Gnss),
+            "nitz" | "network" => Ok(TimeSource::Nitz),
+            _ => Err(format!("'{}' is not a time source (gnss, nitz)", value)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockMode {
+    #[default]
+    Off,
+    // Step the clock when it's off by more than the source's uncertainty
+    Step,
+    // Only slew, for systems where the time must never jump
+    Slew,
+    // Hand the samples to chrony through a SOCK refclock
+    Chrony,
+}
+
+impl FromStr for ClockMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "off" => Ok(ClockMode::Off),
+            "step" => Ok(ClockMode::Step),
+            "slew" => Ok(ClockMode::Slew),
+            "chrony" => Ok(ClockMode::Chrony),
+            _ => Err(format!(
+                "'{}' is not a clock mode (off, step, slew, chrony)",
+                value
+            )),
+        }
+    }
+}
+
+// chrony's struct sock_sample
+#[repr(C)]
+struct ChronySample {
+    tv: libc::timeval,
+    offset: f64,
+    pulse: libc::c_int,
+    leap: libc::c_int,
+    _pad: libc::c_int,
+    magic: libc::c_int,
+}
+
+// Seconds to add to the system clock to get reference, negative when the clock is ahead
+fn offset_from(reference: SystemTime, system: SystemTime) -> f64 {
+    match reference.duration_since(system) {
+        Ok(ahead) => ahead.as_secs_f64(),
+        Err(behind) => -behind.duration().as_secs_f64(),
+    }
+}
+
+fn step_clock(reference: SystemTime) -> io::Result<()> {
+    let since_epoch = reference
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let time = libc::timespec {
+        tv_sec: since_epoch.as_secs() as libc::time_t,
+        tv_nsec: since_epoch.subsec_nanos() as libc::c_long,
+    };
+    // SAFETY: time is a valid timespec for the duration of the call
+    if unsafe { libc::clock_settime(libc::CLOCK_REALTIME, &time) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Whether another daemon (NTP, chrony) disciplines the clock, it clears STA_UNSYNC then
+fn kernel_synced() -> bool {
+    // SAFETY: timex is a plain C struct, zeroed modes only reads the kernel state
+    let mut timex: libc::timex = unsafe { std::mem::zeroed() };
+    if unsafe { libc::adjtimex(&mut timex) } < 0 {
+        return false;
+    }
+    timex.status & libc::STA_UNSYNC == 0
+}
+
+fn slew_clock(offset: f64) -> io::Result<()> {
+    let delta = libc::timeval {
+        tv_sec: offset.trunc() as libc::time_t,
+        tv_usec: (offset.fract() * 1e6) as libc::suseconds_t,
+    };
+    // SAFETY: delta is a valid timeval, the previous adjustment isn't asked for
+    if unsafe { libc::adjtime(&delta, std::ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Keeps the system clock on the best trusted source that currently gives samples.
+// Sources come in trust order, a source that isn't listed is ignored.
+pub struct ClockSync {
+    mode: ClockMode,
+    sources: Vec<TimeSource>,
+    chrony_socket: String,
+    // Source and time of the last sample used
+    last: Option<(TimeSource, Instant)>,
+    // How long a better source has to stay quiet before a worse one is used
+    holdoff: Duration,
+}
+
+impl ClockSync {
+    pub fn new(mode: ClockMode, sources: Vec<TimeSource>, chrony_socket: &str) -> Self {
+        ClockSync {
+            mode,
+            sources,
+            chrony_socket: chrony_socket.to_owned(),
+            last: None,
+            holdoff: SOURCE_HOLDOFF,
+        }
+    }
+
+    fn rank(&self, source: TimeSource) -> Option<usize> {
+        self.sources.iter().position(|trusted| *trusted == source)
+    }
+
+    // Whether a sample from source would be used now
+    pub fn wants(&self, source: TimeSource) -> bool {
+        let rank = match (self.mode, self.rank(source)) {
+            (ClockMode::Off, _) | (_, None) => return false,
+            (_, Some(rank)) => rank,
+        };
+        match self.last {
+            Some((last, at)) => {
+                self.rank(last).is_none_or(|last| rank <= last) || at.elapsed() >= self.holdoff
+            }
+            None => true,
+        }
+    }
+
+    // Feed a reference time read just now from source
+    pub fn sample(&mut self, source: TimeSource, reference: SystemTime) {
+        if !self.wants(source) {
+            return;
+        }
+        let system = SystemTime::now();
+        let offset = offset_from(reference, system);
+        debug!("{:?} time offset {:+.3}s", source, offset);
+
+        let correct = offset.abs() >= source.uncertainty().as_secs_f64();
+        let result = match self.mode {
+            ClockMode::Off => return,
+            ClockMode::Step | ClockMode::Slew if !correct => Ok(()),
+            ClockMode::Step | ClockMode::Slew if kernel_synced() => {
+                debug!(
+                    "Clock synchronized by another daemon, {:?} time not applied",
+                    source
+                );
+                Ok(())
+            }
+            ClockMode::Step => step_clock(reference)
+                .map(|_| info!("Clock stepped by {:+.3}s from {:?} time", offset, source)),
+            ClockMode::Slew => slew_clock(offset)
+                .map(|_| info!("Clock slewed by {:+.3}s from {:?} time", offset, source)),
+            ClockMode::Chrony => self
+                .send_to_chrony(system, offset)
+                .map_err(|e| io::Error::other(e.to_string())),
+        };
+        match result {
+            Ok(_) => self.last = Some((source, Instant::now())),
+            Err(e) => warn!("Can't apply {:?} time: {}", source, e),
+        }
+    }
+
+    fn send_to_chrony(&self, system: SystemTime, offset: f64) -> Result<(), Box<dyn Error>> {
+        let since_epoch = system.duration_since(UNIX_EPOCH)?;
+        let sample = ChronySample {
+            tv: libc::timeval {
+                tv_sec: since_epoch.as_secs() as libc::time_t,
+                tv_usec: since_epoch.subsec_micros() as libc::suseconds_t,
+            },
+            offset,
+            pulse: 0,
+            leap: 0,
+            _pad: 0,
+            magic: CHRONY_SOCK_MAGIC,
+        };
+        // SAFETY: ChronySample is a plain repr(C) struct, viewed as bytes for its whole size
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &sample as *const ChronySample as *const u8,
+                std::mem::size_of::<ChronySample>(),
+            )
+        };
+        let socket = UnixDatagram::unbound()?;
+        socket.send_to(bytes, &self.chrony_socket)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(mode: ClockMode) -> ClockSync {
+        ClockSync::new(
+            mode,
+            vec![TimeSource::Gnss, TimeSource::Nitz],
+            "/nonexistent",
+        )
+    }
+
+    #[test]
+    fn parses_settings() {
+        assert_eq!("GPS".parse(), Ok(TimeSource::Gnss));
+        assert_eq!("network".parse(), Ok(TimeSource::Nitz));
+        assert!("sundial".parse::<TimeSource>().is_err());
+        assert_eq!("Chrony".parse(), Ok(ClockMode::Chrony));
+        assert!("jump".parse::<ClockMode>().is_err());
+    }
+
+    #[test]
+    fn off_or_unlisted_sources_are_ignored() {
+        assert!(!clock(ClockMode::Off).wants(TimeSource::Gnss));
+        let clock = ClockSync::new(ClockMode::Step, vec![TimeSource::Nitz], "/nonexistent");
+        assert!(!clock.wants(TimeSource::Gnss));
+        assert!(clock.wants(TimeSource::Nitz));
+    }
+
+    #[test]
+    fn follows_the_most_trusted_source() {
+        let mut clock = clock(ClockMode::Step);
+        assert!(clock.wants(TimeSource::Gnss));
+        assert!(clock.wants(TimeSource::Nitz));
+
+        clock.last = Some((TimeSource::Nitz, Instant::now()));
+        assert!(clock.wants(TimeSource::Gnss));
+        assert!(clock.wants(TimeSource::Nitz));
+
+        clock.last = Some((TimeSource::Gnss, Instant::now()));
+        assert!(clock.wants(TimeSource::Gnss));
+        assert!(!clock.wants(TimeSource::Nitz));
+    }
+
+    #[test]
+    fn falls_back_once_the_better_source_is_quiet() {
+        let mut clock = clock(ClockMode::Step);
+        clock.last = Some((TimeSource::Gnss, Instant::now()));
+        assert!(!clock.wants(TimeSource::Nitz));
+        clock.holdoff = Duration::ZERO;
+        assert!(clock.wants(TimeSource::Nitz));
+    }
+
+    #[test]
+    fn small_offsets_are_left_alone() {
+        // Well below the GNSS uncertainty, the clock isn't touched but the source counts as heard
+        let mut clock = clock(ClockMode::Step);
+        clock.sample(TimeSource::Gnss, SystemTime::now());
+        assert_eq!(clock.last.map(|(source, _)| source), Some(TimeSource::Gnss));
+        assert!(!clock.wants(TimeSource::Nitz));
+    }
+
+    #[test]
+    fn offset_sign() {
+        let now = SystemTime::now();
+        assert!((offset_from(now + Duration::from_secs(2), now) - 2.0).abs() < 1e-9);
+        assert!((offset_from(now - Duration::from_millis(500), now) + 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::clock_sync::{ClockMode, TimeSource};
+use log::{error, info, warn};
+use modemcli::modem_bearer::BearerConfig;
+use modemcli::modem_command::AtPolicy;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process;
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/modemhandler.conf";
+
+// Daemon settings, read from a "key = value" file, lines starting with '#' are comments
+#[derive(Clone, Debug)]
+pub struct DaemonConfig {
+    pub data: BearerConfig,
+    // Modem.Signal refresh period in seconds, 0 disables extended signal metrics
+    pub signal_refresh_rate: u32,
+    // Seconds between two GNSS location updates, 0 for as soon as available
+    pub gps_refresh_rate: u32,
+    // PIN sent when the SIM asks for one, the modem stays locked without it
+    pub sim_pin: Option<String>,
+    // Seconds without registration before switching SIM slot, 0 disables the failover
+    pub sim_failover_timeout: u64,
+    // Numbers allowed to send SMS commands, comma separated in the file
+    pub sms_whitelist: Vec<String>,
+    // Shared secret of the SMS command HMAC, the command channel is off while empty
+    pub sms_secret: String,
+    // Last nonce accepted per SMS command sender, must survive reboots
+    pub sms_nonce_path: String,
+    // USSD code run periodically to check the prepaid balance, e.g. "*100#"
+    pub ussd_balance_query: Option<String>,
+    // Seconds between two balance queries
+    pub ussd_balance_interval: u64,
+    // Raw AT commands the modem client accepts, from "at_allow" / "at_deny" lines
+    pub at_policy: AtPolicy,
+    // Seconds in FAILED/UNKNOWN state before each recovery step, 0 disables it
+    pub recovery_failed_timeout: u64,
+    // Seconds enabled without registering before each recovery step, 0 disables it
+    pub recovery_registration_timeout: u64,
+    // Seconds between two serving/neighbor cell reports, 0 disables them
+    pub cell_info_interval: u64,
+    // JSON modem inventory, rewritten at startup and whenever the modem re-appears
+    pub inventory_path: String,
+    // SUPL server handed to the modem for A-GPS, e.g. "supl.google.com:7276"
+    pub supl_server: Option<String>,
+    // Cached assistance data (XTRA) injected when the modem shows up
+    pub agps_file: Option<String>,
+    // Seconds the cached assistance file stays usable after it was downloaded
+    pub agps_validity: u64,
+    // Number called when the VCU reports a crash, no call is placed without it
+    pub emergency_number: Option<String>,
+    // Seconds after an emergency call during which incoming calls are answered
+    pub emergency_callback_window: u64,
+    // CAN message and signal raising the emergency call
+    pub crash_can_message: String,
+    pub crash_can_signal: String,
+    // CAN message and signals carrying the call state back
+    pub call_status_can_message: String,
+    pub call_state_can_signal: String,
+    pub call_reason_can_signal: String,
+    pub call_emergency_can_signal: String,
+    // How the system clock follows GNSS / network time
+    pub clock_mode: ClockMode,
+    // Time sources by decreasing trust, comma separated in the file
+    pub time_sources: Vec<TimeSource>,
+    // chrony SOCK refclock socket, used with clock_mode = chrony
+    pub chrony_socket: String,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig {
+            data: BearerConfig::default(),
+            signal_refresh_rate: 10,
+            gps_refresh_rate: 30,
+            sim_pin: None,
+            sim_failover_timeout: 600,
+            sms_whitelist: Vec::new(),
+            sms_secret: String::new(),
+            sms_nonce_path: "/var/lib/modemhandler/sms_nonces".to_owned(),
+            ussd_balance_query: None,
+            ussd_balance_interval: 86400,
+            at_policy: AtPolicy::default(),
+            recovery_failed_timeout: 60,
+            recovery_registration_timeout: 300,
+            cell_info_interval: 60,
+            inventory_path: "/run/modemhandler/inventory.json".to_owned(),
+            supl_server: None,
+            agps_file: None,
+            agps_validity: 259200,
+            emergency_number: None,
+            emergency_callback_window: 600,
+            crash_can_message: "vcu_crash_pkt".to_owned(),
+            crash_can_signal: "crash_detected".to_owned(),
+            call_status_can_message: "tcu_call_status".to_owned(),
+            call_state_can_signal: "call_state".to_owned(),
+            call_reason_can_signal: "call_reason".to_owned(),
+            call_emergency_can_signal: "call_emergency".to_owned(),
+            clock_mode: ClockMode::Step,
+            time_sources: vec![TimeSource::Gnss, TimeSource::Nitz],
+            chrony_socket: "/run/chrony.modemhandler.sock".to_owned(),
+        }
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, Box<dyn Error>> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("'{}' is not a boolean", value).into()),
+    }
+}
+
+impl DaemonConfig {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let mut config = DaemonConfig::default();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    if let Err(e) = config.set(key.trim(), value.trim()) {
+                        return Err(format!("{}:{}: {}", path, number + 1, e).into());
+                    }
+                }
+                None => return Err(format!("{}:{}: expected key = value", path, number + 1).into()),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Load the file given with "--config <path>", or the default one.
+    // Without a file every setting is at its default, a broken file stops the daemon.
+    pub fn from_args() -> Self {
+        let args: Vec<String> = env::args().collect();
+        let path = args
+            .iter()
+            .position(|arg| arg == "--config")
+            .and_then(|index| args.get(index + 1))
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_CONFIG_PATH);
+
+        if !Path::new(path).exists() {
+            warn!("No configuration {}, using defaults", path);
+            return DaemonConfig::default();
+        }
+        match DaemonConfig::load(path) {
+            Ok(config) => {
+                info!("Configuration loaded from {}", path);
+                config
+            }
+            Err(e) => {
+                error!("Can't load configuration {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        match key {
+            "apn" => self.data.apn = value.to_owned(),
+            "ip_type" => self.data.ip_type = value.parse()?,
+            "auth" => self.data.auth = value.parse()?,
+            "user" => self.data.user = Some(value.to_owned()),
+            "password" => self.data.password = Some(value.to_owned()),
+            "allow_roaming" => self.data.allow_roaming = parse_bool(value)?,
+            "signal_refresh_rate" => self.signal_refresh_rate = value.parse()?,
+            "gps_refresh_rate" => self.gps_refresh_rate = value.parse()?,
+            "sim_pin" => self.sim_pin = Some(value.to_owned()),
+            "sim_failover_timeout" => self.sim_failover_timeout = value.parse()?,
+            "sms_whitelist" => {
+                self.sms_whitelist = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|number| !number.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            }
+            "sms_secret" => self.sms_secret = value.to_owned(),
+            "sms_nonce_path" => self.sms_nonce_path = value.to_owned(),
+            "ussd_balance_query" => self.ussd_balance_query = Some(value.to_owned()),
+            "ussd_balance_interval" => self.ussd_balance_interval = value.parse()?,
+            // Both may be repeated, each line adds one prefix / pattern
+            "at_allow" => self.at_policy = self.at_policy.clone().allow(value),
+            "at_deny" => self.at_policy = self.at_policy.clone().deny(value)?,
+            "recovery_failed_timeout" => self.recovery_failed_timeout = value.parse()?,
+            "recovery_registration_timeout" => {
+                self.recovery_registration_timeout = value.parse()?
+            }
+            "cell_info_interval" => self.cell_info_interval = value.parse()?,
+            "inventory_path" => self.inventory_path = value.to_owned(),
+            "supl_server" => self.supl_server = Some(value.to_owned()),
+            "agps_file" => self.agps_file = Some(value.to_owned()),
+            "agps_validity" => self.agps_validity = value.parse()?,
+            "emergency_number" => self.emergency_number = Some(value.to_owned()),
+            "emergency_callback_window" => self.emergency_callback_window = value.parse()?,
+            "crash_can_message" => self.crash_can_message = value.to_owned(),
+            "crash_can_signal" => self.crash_can_signal = value.to_owned(),
+            "call_status_can_message" => self.call_status_can_message = value.to_owned(),
+            "call_state_can_signal" => self.call_state_can_signal = value.to_owned(),
+            "call_reason_can_signal" => self.call_reason_can_signal = value.to_owned(),
+            "call_emergency_can_signal" => self.call_emergency_can_signal = value.to_owned(),
+            "clock_mode" => self.clock_mode = value.parse()?,
+            "time_sources" => {
+                self.time_sources = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|source| !source.is_empty())
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?
+            }
+            "chrony_socket" => self.chrony_socket = value.to_owned(),
+            _ => warn!("Unknown configuration key '{}'", key),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use modemcli::modem_bearer::{BearerAuth, IpType};
+
+    fn load(test: &str, text: &str) -> Result<DaemonConfig, Box<dyn Error>> {
+        let path =
+            std::env::temp_dir().join(format!("modemhandler-{}-{}.conf", std::process::id(), test));
+        std::fs::write(&path, text).unwrap();
+        let config = DaemonConfig::load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(path);
+        config
+    }
+
+    #[test]
+    fn reads_settings() {
+        let config = load(
+            "settings",
+            "# data\n\
+             apn = internet.example\n\
+             ip_type = ipv4v6\n\
+             auth = chap\n\
+             allow_roaming = yes\n\
+             \n\
+             sms_whitelist = +33612345678, +33 7 00 00 00 00,\n\
+             at_allow = AT+QTEMP\n\
+             time_sources = nitz, gnss\n\
+             clock_mode = chrony\n\
+             sim_failover_timeout = 0\n\
+             emergency_number = 112\n",
+        )
+        .unwrap();
+        assert_eq!(config.data.apn, "internet.example");
+        assert_eq!(config.data.ip_type, IpType::Ipv4v6);
+        assert_eq!(config.data.auth, BearerAuth::Chap);
+        assert!(config.data.allow_roaming);
+        assert_eq!(
+            config.sms_whitelist,
+            vec!["+33612345678", "+33 7 00 00 00 00"]
+        );
+        assert!(config.at_policy.check("AT+QTEMP").is_ok());
+        assert_eq!(
+            config.time_sources,
+            vec![TimeSource::Nitz, TimeSource::Gnss]
+        );
+        assert_eq!(config.clock_mode, ClockMode::Chrony);
+        assert_eq!(config.sim_failover_timeout, 0);
+        assert_eq!(config.emergency_number.as_deref(), Some("112"));
+    }
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let config = load("defaults", "unknown_key = 1\n").unwrap();
+        let default = DaemonConfig::default();
+        assert_eq!(config.sim_failover_timeout, default.sim_failover_timeout);
+        assert_eq!(config.clock_mode, ClockMode::Step);
+        assert_eq!(config.emergency_number, None);
+        assert!(config.at_policy.check("AT+QTEMP").is_err());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = load("bad_value", "apn = internet\nallow_roaming = maybe\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with(":2: 'maybe' is not a boolean"), "{}", error);
+        let error = load("bad_line", "apn internet\n").unwrap_err().to_string();
+        assert!(error.ends_with(":1: expected key = value"), "{}", error);
+        assert!(load("bad_source", "time_sources = gnss, sundial\n").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use log::{info, warn};
+use modemcli::modem_cli::IonModemCli;
+use modemcli::modem_voice::CallState;
+use std::time::{Duration, Instant};
+
+// Wait between two attempts while the emergency call can't be placed
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 5;
+// How often the modem is asked whether the emergency call still exists
+const CALL_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// Places a call to the configured number when the crash signal rises, dialing again
+// (up to MAX_ATTEMPTS) while it fails or ends without having been answered. Incoming
+// calls are answered during the call and for callback_window after it ended, so the
+// assistance centre can call back.
+pub struct EmergencyCall {
+    number: Option<String>,
+    callback_window: Duration,
+    crash: bool,
+    pending: bool,
+    last_attempt: Option<Instant>,
+    attempts: u32,
+    // A dial is on its way, waiting for dialed()
+    dialing: bool,
+    // Object path of the emergency call while it holds the line
+    call: Option<String>,
+    answered: bool,
+    last_check: Option<Instant>,
+    ended: Option<Instant>,
+}
+
+impl EmergencyCall {
+    pub fn new(number: Option<String>, callback_window: Duration) -> Self {
+        EmergencyCall {
+            number,
+            callback_window,
+            crash: false,
+            pending: false,
+            last_attempt: None,
+            attempts: 0,
+            dialing: false,
+            call: None,
+            answered: false,
+            last_check: None,
+            ended: None,
+        }
+    }
+
+    // Feed every value of the crash signal, a call is due on its rising edge
+    pub fn crash_signal(&mut self, crash: bool) {
+        if crash && !self.crash {
+            match self.number {
+                Some(_) if self.call.is_none() => {
+                    warn!("Crash detected, placing emergency call");
+                    self.pending = true;
+                    self.last_attempt = None;
+                    self.attempts = 0;
+                }
+                Some(_) => info!("Crash detected, emergency call already ongoing"),
+                None => warn!("Crash detected, no emergency number configured"),
+            }
+        }
+        self.crash = crash;
+    }
+
+    // Number to dial when a call is due, to be called whenever the modem is ready.
+    // The outcome of the dial is fed back through dialed().
+    pub fn poll(&mut self, modem_cli: &IonModemCli) -> Option<String> {
+        self.check_call(modem_cli);
+        if self.dialing || !self.pending {
+            return None;
+        }
+        if self
+            .last_attempt
+            .is_some_and(|last| last.elapsed() < RETRY_INTERVAL)
+        {
+            return None;
+        }
+        let number = self.number.clone()?;
+        self.last_attempt = Some(Instant::now());
+        self.attempts += 1;
+        self.dialing = true;
+        Some(number)
+    }
+
+    pub fn dialed(&mut self, number: &str, result: Result<String, String>) {
+        self.dialing = false;
+        match result {
+            Ok(call) => {
+                info!("Emergency call {} to {} started", call, number);
+                self.pending = false;
+                self.answered = false;
+                self.last_check = Some(Instant::now());
+                self.call = Some(call);
+            }
+            Err(e) => {
+                warn!(
+                    "Can't place emergency call to {} (attempt {}): {}",
+                    number, self.attempts, e
+                );
+                self.give_up_after_last_attempt();
+            }
+        }
+    }
+
+    // A call dropped without a Terminated state change, e.g. by a modem reset, counts as ended
+    fn check_call(&mut self, modem_cli: &IonModemCli) {
+        let path = match &self.call {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        if self
+            .last_check
+            .is_some_and(|last| last.elapsed() < CALL_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+        match modem_cli.ongoing_calls() {
+            Ok(calls) if !calls.iter().any(|call| call.path == path) => self.ended(&path),
+            Ok(_) => {}
+            Err(e) => warn!("Can't check emergency call {}: {}", path, e),
+        }
+    }
+
+    // The modem went away or was reset, whatever call it held is gone with it
+    pub fn modem_lost(&mut self) {
+        if let Some(path) = self.call.clone() {
+            self.ended(&path);
+        }
+    }
+
+    fn ended(&mut self, path: &str) {
+        self.call = None;
+        self.ended = Some(Instant::now());
+        if self.answered {
+            info!("Emergency call {} ended", path);
+        } else {
+            warn!(
+                "Emergency call {} ended unanswered (attempt {})",
+                path, self.attempts
+            );
+            self.pending = true;
+            self.give_up_after_last_attempt();
+        }
+    }
+
+    fn give_up_after_last_attempt(&mut self) {
+        if self.attempts >= MAX_ATTEMPTS {
+            warn!("Emergency call failed {} times, giving up", self.attempts);
+            self.pending = false;
+        }
+    }
+
+    // Feed every call state change
+    pub fn call_state(&mut self, path: &str, state: CallState) {
+        if !self.is_emergency_call(path) {
+            return;
+        }
+        match state {
+            CallState::Active => self.answered = true,
+            CallState::Terminated => self.ended(path),
+            _ => {}
+        }
+    }
+
+    pub fn is_emergency_call(&self, path: &str) -> bool {
+        self.call.as_deref() == Some(path)
+    }
+
+    // Whether an incoming call is answered automatically
+    pub fn answers_incoming(&self) -> bool {
+        self.call.is_some()
+            || self
+                .ended
+                .is_some_and(|ended| ended.elapsed() < self.callback_window)
+    }
+}
@@ -1,23 +1,633 @@
-use std::thread;
-use std::time::Duration;
-use log::{debug, trace, error, info, warn};
+mod clock_sync;
+mod config;
+mod emergency_call;
+mod modem_worker;
+mod recovery;
+mod sim_failover;
+mod sms_commands;
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+use log::{debug, error, trace, info, warn};
+use modemcli::modem_bearer::BearerConfig;
 use modemcli::modem_cli::*;
+use modemcli::modem_error::ModemError;
+use modemcli::modem_events::ModemEvent;
+use modemcli::modem_state::{ModemState, StateFailedReason};
+use modemcli::modem_location::{AssistanceDataTypes, LocationConfig, LocationSources};
+use modemcli::nmea::GnssFix;
+use modemcli::modem_sim::ModemLock;
+use modemcli::modem_sms::{Sms, SmsState};
+use modemcli::modem_voice::{CallDirection, CallState, CallStateReason};
 use canutils::can_utils::*;
 use logging::logging::*;
+use clock_sync::{ClockSync, TimeSource};
+use config::DaemonConfig;
+use emergency_call::EmergencyCall;
+use modem_worker::{Job, JobResult, ModemWorker};
+use recovery::{Health, RecoveryLadder};
+use sim_failover::SimFailover;
+use sms_commands::{SmsCommand, SmsCommandChannel, AUDIT_TARGET};
 // use socketcan::{CanSocket, EmbeddedFrame, Socket};
 
+// Longest time the loop waits on CAN before serving modem events
+const CAN_READ_TIMEOUT: Duration = Duration::from_millis(100);
+const MODEM_EVENT_TIMEOUT: Duration = Duration::from_millis(10);
+const RECOVERY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Wait before applying the user settings again after a step failed
+const SETTINGS_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+// Wait before injecting assistance data again after a failure or while its age is unknown
+const AGPS_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+
+// False when connecting or disconnecting failed and has to be tried again
+fn apply_data_setting(
+    modem_cli: &IonModemCli,
+    data: &BearerConfig,
+    state: ModemState,
+    vehicle_cell_enable: bool,
+    data_bearer: &mut Option<String>,
+) -> bool {
+    // Roaming policy: allow-roaming only guards the connection set-up, an established bearer
+    // survives moving onto a visited network, so it's torn down here
+    let roaming = modem_cli
+        .registration_state()
+        .is_ok_and(|registration| registration.is_roaming());
+    if roaming && !data.allow_roaming {
+        info!("Roaming, data blocked by configuration");
+    }
+
+    if vehicle_cell_enable && (data.allow_roaming || !roaming) {
+        trace!("Enable Data LTE based on usersetting");
+        // Simple.Connect needs a registered modem, it's retried on the next state change
+        if state == ModemState::Registered {
+            match modem_cli.connect_data(data) {
+                Ok(bearer) => {
+                    info!("Data connected on {}", bearer);
+                    match modem_cli.bearer_status(&bearer) {
+                        Ok(status) => info!("Bearer: {:?}", status),
+                        Err(e) => warn!("Can't read bearer status: {}", e),
+                    }
+                    *data_bearer = Some(bearer);
+                }
+                Err(e) => {
+                    warn!("Can't connect data: {}", e);
+                    return false;
+                }
+            }
+        }
+    } else if state.is_connected() || data_bearer.is_some() {
+        trace!("Disable Data LTE based on usersetting");
+        match modem_cli.disconnect_data(data_bearer.as_deref()) {
+            Ok(_) => info!("Data disconnected"),
+            Err(e) => {
+                warn!("Can't disconnect data: {}", e);
+                return false;
+            }
+        }
+        *data_bearer = None;
+    }
+    true
+}
+
+// Send the configured PIN once, a rejected PIN is never retried so the SIM doesn't end up PUK locked.
+// False when the modem couldn't be asked and it's worth trying again.
+fn unlock_sim(modem_cli: &IonModemCli, sim_pin: Option<&str>, pin_rejected: &mut bool) -> bool {
+    let lock = match modem_cli.unlock_required() {
+        Ok(lock) => lock,
+        Err(e) => {
+            warn!("Can't read SIM lock: {}", e);
+            return false;
+        }
+    };
+    match (lock, sim_pin) {
+        (ModemLock::SimPin, Some(pin)) if !*pin_rejected => {
+            // Leave the last attempt to a human
+            if let Ok(Some(retries)) = modem_cli.pin_retries() {
+                if retries <= 1 {
+                    warn!("SIM PIN has {} attempt left, not sending it", retries);
+                    return true;
+                }
+            }
+            match modem_cli.send_pin(pin) {
+                Ok(_) => info!("SIM unlocked"),
+                Err(e) if e.mm_error().is_some() => {
+                    warn!("SIM PIN rejected: {}", e);
+                    *pin_rejected = true;
+                }
+                Err(e) => {
+                    warn!("Can't send SIM PIN: {}", e);
+                    return false;
+                }
+            }
+        }
+        (ModemLock::SimPin, _) => warn!("SIM locked, no usable PIN configured"),
+        (lock, _) => warn!("Modem locked ({}), can't unlock it", lock),
+    }
+    true
+}
+
+// False when a step failed, the settings are applied again after SETTINGS_RETRY_INTERVAL then
+fn apply_user_settings(
+    modem_cli: &IonModemCli,
+    config: &DaemonConfig,
+    vehicle_gps_enable: bool,
+    vehicle_cell_enable: bool,
+    data_bearer: &mut Option<String>,
+    pin_rejected: &mut bool,
+) -> bool {
+    info!(
+        "Location: {:?}, ModemState: {:?}, SignalStrength: {:?}",
+        modem_cli.location_config(),
+        modem_cli.state(),
+        modem_cli.get_signal_strength()
+    );
+    let state = modem_cli.state();
+    let mut applied = match &state {
+        Ok(ModemState::Disabled) => match modem_cli.setup_modem_enable(true) {
+            Ok(_) => {
+                trace!("modem enable success");
+                true
+            }
+            Err(e) => {
+                trace!("Can't enable modem: {}", e);
+                false
+            }
+        },
+        Ok(ModemState::Locked) => unlock_sim(modem_cli, config.sim_pin.as_deref(), pin_rejected),
+        Ok(ModemState::Failed) => {
+            warn!("Modem failed: {:?}", modem_cli.failed_reason());
+            true
+        }
+        Ok(state) => {
+            trace!("Modem state: {}", state);
+            true
+        }
+        Err(ModemError::Dbus(e)) => {
+            warn!("D-Bus unavailable: {}", e);
+            false
+        }
+        Err(e) => {
+            warn!("Can't read modem state: {}", e);
+            false
+        }
+    };
+
+    // The serving cell keeps being reported with GNSS off
+    let mut sources = LocationSources::THREEGPP_LAC_CI | LocationSources::GPS_RAW;
+    if vehicle_gps_enable {
+        trace!("Enable GPS base on user setting");
+        sources |= LocationSources::GPS_NMEA;
+    }
+    let location = LocationConfig {
+        sources,
+        signal_location: true,
+        refresh_rate: config.gps_refresh_rate,
+    };
+    match modem_cli.is_location_enabled(&location) {
+        Ok(true) => {}
+        Ok(false) => match modem_cli.apply_location_config(&location) {
+            Ok(kept) if kept == location => trace!("location setup success"),
+            // Asking again won't change the modem's mind
+            Ok(kept) => warn!(
+                "Location config {:?} requested, modem kept {:?}",
+                location, kept
+            ),
+            Err(e) => {
+                info!("Can't perfom action: {}", e);
+                applied = false;
+            }
+        },
+        Err(e) => {
+            trace!("Can't read location config: {}", e);
+            applied = false;
+        }
+    }
+
+    if let Ok(state) = state {
+        applied &= apply_data_setting(
+            modem_cli,
+            &config.data,
+            state,
+            vehicle_cell_enable,
+            data_bearer,
+        );
+    }
+    applied
+}
+
+fn status_report(
+    modem_cli: &IonModemCli,
+    vehicle_gps_enable: bool,
+    vehicle_cell_enable: bool,
+) -> String {
+    let state = modem_cli
+        .state()
+        .map(|state| state.to_string())
+        .unwrap_or_else(|_| "unknown".to_owned());
+    let registration = modem_cli
+        .registration_state()
+        .map(|state| state.to_string())
+        .unwrap_or_else(|_| "unknown".to_owned());
+    let operator = modem_cli.operator_name().unwrap_or_default();
+    let signal = modem_cli
+        .get_signal_quality()
+        .map(|quality| quality.percent)
+        .unwrap_or(0);
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+    format!(
+        "state={} reg={} op={} signal={}% gps={} data={}",
+        state,
+        registration,
+        operator,
+        signal,
+        on_off(vehicle_gps_enable),
+        on_off(vehicle_cell_enable)
+    )
+}
+
+// Run an authenticated SMS command, returns the reply text
+fn run_sms_command(
+    modem_cli: &IonModemCli,
+    command: SmsCommand,
+    vehicle_gps_enable: &mut bool,
+    vehicle_cell_enable: &mut bool,
+) -> String {
+    match command {
+        SmsCommand::GpsOn | SmsCommand::GpsOff => {
+            *vehicle_gps_enable = command == SmsCommand::GpsOn
+        }
+        SmsCommand::DataOn | SmsCommand::DataOff => {
+            *vehicle_cell_enable = command == SmsCommand::DataOn
+        }
+        // Queued by handle_sms() after the reply, the modem object goes away with the reset
+        SmsCommand::RebootModem => {}
+        SmsCommand::Status => {
+            return status_report(modem_cli, *vehicle_gps_enable, *vehicle_cell_enable)
+        }
+    }
+    format!("{} OK", command.as_str())
+}
+
+// One line per cell, tagged with the last GNSS position for coverage mapping
+fn log_cell_info(modem_cli: &IonModemCli, position: Option<&GnssFix>) {
+    let cells = match modem_cli.cell_info() {
+        Ok(cells) => cells,
+        Err(e) => {
+            trace!("Can't read cell info: {}", e);
+            return;
+        }
+    };
+    let position = match position.map(|fix| (fix.latitude, fix.longitude)) {
+        Some((Some(latitude), Some(longitude))) => format!("{:.6},{:.6}", latitude, longitude),
+        _ => "-".to_owned(),
+    };
+    for cell in cells {
+        info!(
+            "Cell {} {} op={} ci={:?} pci={:?} tac={:?} arfcn={:?} rsrp={:?} rsrq={:?} at {}",
+            if cell.serving { "serving" } else { "neighbor" },
+            cell.rat,
+            cell.operator_id,
+            cell.ci,
+            cell.pci,
+            cell.tac,
+            cell.arfcn,
+            cell.rsrp,
+            cell.rsrq,
+            position
+        );
+    }
+}
+
+fn publish_inventory(modem_cli: &IonModemCli, path: &str) -> Result<(), Box<dyn Error>> {
+    let inventory = modem_cli.inventory()?;
+    let json = serde_json::to_string_pretty(&inventory)?;
+    info!("Modem inventory: {}", serde_json::to_string(&inventory)?);
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, json)?;
+    Ok(())
+}
+
+// Skipped when the file is older than its validity window, stale orbits slow the fix down
+// Ok(false) when it has to be tried again later: the file is newer than the system clock,
+// which isn't set yet, so its age is unknown
+fn inject_agps(
+    modem_cli: &IonModemCli,
+    worker: &ModemWorker,
+    path: &str,
+    validity: Duration,
+) -> Result<bool, Box<dyn Error>> {
+    if !modem_cli
+        .supported_assistance_data()?
+        .contains(AssistanceDataTypes::XTRA)
+    {
+        info!("Modem doesn't take XTRA assistance data");
+        return Ok(true);
+    }
+    if let Ok(servers) = modem_cli.assistance_data_servers() {
+        trace!("Assistance data servers: {:?}", servers);
+    }
+    let age = match fs::metadata(path)?.modified()?.elapsed() {
+        Ok(age) => age,
+        Err(_) => {
+            info!(
+                "Assistance data {} is dated after the system clock, waiting for the clock",
+                path
+            );
+            return Ok(false);
+        }
+    };
+    if age >= validity {
+        info!(
+            "Assistance data {} is {}h old, not injected",
+            path,
+            age.as_secs() / 3600
+        );
+        return Ok(true);
+    }
+    let data = fs::read(path)?;
+    if !worker.submit(Job::InjectAssistance {
+        path: path.to_owned(),
+        data,
+    }) {
+        return Err("modem worker stopped".into());
+    }
+    Ok(true)
+}
+
+fn setup_supl(modem_cli: &IonModemCli, config: &DaemonConfig) {
+    if let Some(server) = config.supl_server.as_deref() {
+        match modem_cli.set_supl_server(server) {
+            Ok(_) => info!("SUPL server set to {}", server),
+            Err(e) => warn!("Can't set SUPL server {}: {}", server, e),
+        }
+    }
+}
+
+// Call state (MMCallState), state reason (MMCallStateReason) and 1 for the emergency call,
+// encoded with the DBC layout of the call status message
+fn report_call_state(
+    can: &CanUtils,
+    config: &DaemonConfig,
+    state: CallState,
+    reason: CallStateReason,
+    emergency: bool,
+) {
+    let signals = [
+        (config.call_state_can_signal.as_str(), state as u8 as f64),
+        (config.call_reason_can_signal.as_str(), reason as u8 as f64),
+        (
+            config.call_emergency_can_signal.as_str(),
+            emergency as u8 as f64,
+        ),
+    ];
+    if let Err(e) = can.send_signals(&config.call_status_can_message, &signals) {
+        warn!("Can't report call state on CAN: {}", e);
+    }
+}
+
+// The emergency call can't work with a DBC that lacks its messages, better not start at all
+fn check_emergency_can(can: &CanUtils, config: &DaemonConfig) -> Result<(), String> {
+    let signals = [
+        (&config.crash_can_message, &config.crash_can_signal),
+        (
+            &config.call_status_can_message,
+            &config.call_state_can_signal,
+        ),
+        (
+            &config.call_status_can_message,
+            &config.call_reason_can_signal,
+        ),
+        (
+            &config.call_status_can_message,
+            &config.call_emergency_can_signal,
+        ),
+    ];
+    match signals
+        .iter()
+        .find(|(message, signal)| !can.has_signal(message, signal))
+    {
+        Some((message, signal)) => Err(format!(
+            "signal {} of {} not in the CAN database",
+            signal, message
+        )),
+        None => Ok(()),
+    }
+}
+
+fn modem_health(modem_cli: &IonModemCli) -> Health {
+    match modem_cli.state() {
+        // Nothing the ladder can do about a missing SIM
+        Ok(ModemState::Failed) => match modem_cli.failed_reason() {
+            Ok(StateFailedReason::SimMissing) => Health::Healthy,
+            _ => Health::Failed,
+        },
+        Ok(ModemState::Unknown) => Health::Failed,
+        Ok(ModemState::Enabled) | Ok(ModemState::Searching) => match modem_cli.registration_state()
+        {
+            Ok(registration) if registration.is_registered() => Health::Healthy,
+            _ => Health::RegistrationStalled,
+        },
+        _ => Health::Healthy,
+    }
+}
+
+// One-shot query, a menu sent back by the network is cancelled when the session is dropped.
+// Returns false when the query has to wait for the modem to register.
+// The session runs on the worker, its reply comes back with the job results
+fn query_balance(modem_cli: &IonModemCli, worker: &ModemWorker, query: &str) -> bool {
+    match modem_cli.registration_state() {
+        Ok(registration) if registration.is_registered() => {}
+        _ => return false,
+    }
+    worker.submit(Job::Ussd(query.to_owned()))
+}
+
+fn log_job_result(result: JobResult) {
+    match result {
+        JobResult::Ussd {
+            code,
+            reply: Ok(reply),
+        } => info!("Balance ({}): {}", code, reply),
+        JobResult::Ussd {
+            code,
+            reply: Err(e),
+        } => warn!("Balance query {} failed: {}", code, e),
+        JobResult::InjectAssistance {
+            path,
+            result: Ok(len),
+        } => info!("Injected {} bytes of assistance data from {}", len, path),
+        JobResult::InjectAssistance {
+            path,
+            result: Err(e),
+        } => warn!("Can't inject assistance data {}: {}", path, e),
+        // Logged by EmergencyCall::dialed()
+        JobResult::Dial { .. } => {}
+        JobResult::AcceptCall {
+            number,
+            result: Ok(_),
+        } => info!("Answered call from {}", number),
+        JobResult::AcceptCall {
+            number,
+            result: Err(e),
+        } => warn!("Can't answer call from {}: {}", number, e),
+        JobResult::SendText {
+            number,
+            result: Ok(_),
+        } => trace!("SMS sent to {}", number),
+        JobResult::SendText {
+            number,
+            result: Err(e),
+        } => warn!(target: AUDIT_TARGET, "Can't reply to {}: {}", number, e),
+        JobResult::DeleteSms {
+            path,
+            result: Ok(_),
+        } => trace!("SMS {} deleted", path),
+        JobResult::DeleteSms {
+            path,
+            result: Err(e),
+        } => warn!("Can't delete SMS {}: {}", path, e),
+        JobResult::Reset {
+            requested_by,
+            result: Ok(_),
+        } => info!(target: AUDIT_TARGET, "Modem reset for {}", requested_by),
+        JobResult::Reset {
+            requested_by,
+            result: Err(e),
+        } => warn!(
+            target: AUDIT_TARGET,
+            "'{}' from {} failed: {}",
+            SmsCommand::RebootModem.as_str(),
+            requested_by,
+            e
+        ),
+    }
+}
+
+// Only SMS that authenticated as a command are deleted, anything else stays in storage
+// for whoever reads it next. The reply, the deletion and a reset go through the worker in
+// that order. Returns true when the command changed a user setting.
+fn handle_sms(
+    modem_cli: &IonModemCli,
+    worker: &ModemWorker,
+    sms_channel: Option<&mut SmsCommandChannel>,
+    sms: &Sms,
+    vehicle_gps_enable: &mut bool,
+    vehicle_cell_enable: &mut bool,
+) -> bool {
+    // The body may carry a command token, it stays out of the main log
+    info!("SMS from {}", sms.number);
+    debug!(target: AUDIT_TARGET, "SMS from {}: {}", sms.number, sms.text);
+    // Nothing is sent back to unknown senders
+    let command = sms_channel.and_then(|channel| channel.authenticate(&sms.number, &sms.text).ok());
+    let Some(command) = command else {
+        info!(target: AUDIT_TARGET, "SMS {} from {} left in storage", sms.path, sms.number);
+        return false;
+    };
+
+    let settings = (*vehicle_gps_enable, *vehicle_cell_enable);
+    let reply = run_sms_command(modem_cli, command, vehicle_gps_enable, vehicle_cell_enable);
+    info!(target: AUDIT_TARGET, "'{}' from {}: {}", command.as_str(), sms.number, reply);
+    worker.submit(Job::SendText {
+        number: sms.number.clone(),
+        text: reply,
+    });
+    worker.submit(Job::DeleteSms(sms.path.clone()));
+    if command == SmsCommand::RebootModem {
+        worker.submit(Job::Reset {
+            requested_by: sms.number.clone(),
+        });
+    }
+    settings != (*vehicle_gps_enable, *vehicle_cell_enable)
+}
+
 fn main() {
     let console_log = MyLogging::default();
     console_log.init_logger();
-    
+    let config = DaemonConfig::from_args();
+
     let can_conn = CanUtils::new("/usr/share/can-dbcs/consolidated.dbc".to_string(), "vcan0");
-    
-    let can_filters: Vec<&str> = vec!["vcu_ble_pkt_1"];
+
+    if config.emergency_number.is_some() {
+        if let Err(e) = check_emergency_can(can_conn.as_ref().expect("REASON"), &config) {
+            error!("Emergency call enabled but {}", e);
+            process::exit(1);
+        }
+    }
+
+    let can_filters: Vec<&str> = vec!["vcu_ble_pkt_1", &config.crash_can_message];
     can_conn.as_ref().expect("REASON").set_can_filters_from_can_names(&can_filters);
+    if let Err(e) = can_conn
+        .as_ref()
+        .expect("REASON")
+        .set_read_timeout(CAN_READ_TIMEOUT)
+    {
+        warn!("Can't set CAN read timeout: {}", e);
+    }
 
-    let mut modem_cli = IonModemCli::default();
+    let mut modem_cli = IonModemCli::builder()
+        .at_policy(config.at_policy.clone())
+        .build();
     trace!("Modem CLI: {:?}", modem_cli);
 
+    // Calls have their own worker so that an emergency call doesn't wait behind a USSD
+    // session or an assistance data injection
+    let (worker, call_worker) = match (
+        ModemWorker::spawn("modem-worker", modem_cli.to_builder()),
+        ModemWorker::spawn("modem-calls", modem_cli.to_builder()),
+    ) {
+        (Ok(worker), Ok(call_worker)) => (worker, call_worker),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Can't start modem worker: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut modem_events: Option<Receiver<ModemEvent>> = None;
+    let mut settings_dirty = true;
+    let mut settings_retry: Option<Instant> = None;
+    let mut data_bearer: Option<String> = None;
+    let mut pin_rejected = false;
+    let mut sim_failover = SimFailover::new(Duration::from_secs(config.sim_failover_timeout));
+    let mut sms_channel = SmsCommandChannel::new(
+        &config.sms_whitelist,
+        &config.sms_secret,
+        &config.sms_nonce_path,
+    );
+    let mut pending_sms: Vec<String> = Vec::new();
+    // Messages received while we weren't subscribed are read from the storage
+    let mut sms_scan_due = true;
+    let mut last_balance_query: Option<Instant> = None;
+    let mut recovery = RecoveryLadder::new(
+        Duration::from_secs(config.recovery_failed_timeout),
+        Duration::from_secs(config.recovery_registration_timeout),
+    );
+    let mut last_recovery_check = Instant::now();
+    let mut last_cell_info: Option<Instant> = None;
+    let mut last_position: Option<GnssFix> = None;
+    // Modem the per-modem setup below was done for
+    let mut bound_modem: Option<String> = None;
+    // SIM and operator logging and the signal refresh, done again for every new modem
+    let mut modem_setup_due = true;
+    let mut inventory_due = true;
+    let mut agps_due = true;
+    // When the assistance data is injected next, None once it's done
+    let mut agps_retry: Option<Instant> = None;
+    let mut network_time_due = true;
+    let mut clock = ClockSync::new(
+        config.clock_mode,
+        config.time_sources.clone(),
+        &config.chrony_socket,
+    );
+    let mut emergency = EmergencyCall::new(
+        config.emergency_number.clone(),
+        Duration::from_secs(config.emergency_callback_window),
+    );
     let mut vehicle_gps_enable = true;
     let mut vehicle_cell_enable = true;
     loop {
@@ -27,13 +637,16 @@ fn main() {
                 for (signal, value) in frame {
                     match signal.to_string().as_str() {
                         "ble_cellular" => {
+                            settings_dirty |= vehicle_cell_enable != (value != 0.0);
                             vehicle_cell_enable = value != 0.0;
-                            trace!("Cell: {}", vehicle_gps_enable);
+                            trace!("Cell: {}", vehicle_cell_enable);
                         }
                         "ble_gps" => {
+                            settings_dirty |= vehicle_gps_enable != (value != 0.0);
                             vehicle_gps_enable = value != 0.0;
                             trace!("Gps: {}", vehicle_gps_enable);
                         }
+                        name if name == config.crash_can_signal => emergency.crash_signal(value != 0.0),
                         _ => {}
                     }
                 }
@@ -43,41 +656,294 @@ fn main() {
         }
 
         if modem_cli.waiting_for_ready() {
-            info!("Location: {}, ModemEnable: {}, SignalQuality: {}", modem_cli.is_location_enabled(), modem_cli.is_modem_enabled(), modem_cli.get_signal_strength());
-            if !modem_cli.is_modem_enabled() {
-                match modem_cli.setup_modem_enable(true) {
-                    Ok(_) => {trace!("modem enable success")}
-                    Err(e) => {trace!("Can't enable modem: {:?}", e)}
+            // Other modems coming and going don't concern us, only the one we're bound to
+            let current = modem_cli.modem_path().ok();
+            if bound_modem.as_deref() != current {
+                bound_modem = current.map(str::to_owned);
+                emergency.modem_lost();
+                // Bearers belong to the modem object, a new one has none connected yet
+                data_bearer = None;
+                // It may hold another SIM, the last PIN attempt is still kept by unlock_sim()
+                pin_rejected = false;
+                sim_failover.reset();
+                modem_setup_due = true;
+                inventory_due = true;
+                agps_due = true;
+                network_time_due = true;
+                settings_dirty = true;
+                sms_scan_due = true;
+            }
+            if modem_setup_due {
+                modem_setup_due = false;
+                match modem_cli.list_modems() {
+                    Ok(modems) => {
+                        for modem in modems {
+                            info!("Modem {}: {} {} [{}] on {}", modem.path, modem.manufacturer, modem.model, modem.equipment_identifier, modem.device);
+                        }
+                    }
+                    Err(e) => warn!("Can't list modems: {}", e),
+                }
+                match modem_cli.sim_info() {
+                    Ok(sim) => info!("SIM {}: IMSI {}, operator {} ({})", sim.sim_identifier, sim.imsi, sim.operator_name, sim.operator_identifier),
+                    Err(e) => warn!("Can't read SIM: {}", e),
+                }
+                match (modem_cli.operator_name(), modem_cli.operator_code()) {
+                    (Ok(name), Ok(code)) => info!("Operator: {} ({})", name, code),
+                    (Err(e), _) | (_, Err(e)) => trace!("Can't read operator: {}", e),
+                }
+                match (modem_cli.current_modes(), modem_cli.current_bands()) {
+                    (Ok(modes), Ok(bands)) => info!("Modes: {:?} (preferred {:?}), bands: {}", modes.allowed, modes.preferred, bands.iter().map(|band| band.to_string()).collect::<Vec<_>>().join(",")),
+                    (Err(e), _) | (_, Err(e)) => trace!("Can't read modes/bands: {}", e),
+                }
+                if let Ok(slots) = modem_cli.sim_slots() {
+                    for slot in slots {
+                        match slot.sim {
+                            Some(sim) => info!("SIM slot {}{}: {:?} {}", slot.slot, if slot.primary { " (primary)" } else { "" }, sim.sim_type, sim.sim_identifier),
+                            None => info!("SIM slot {}: empty", slot.slot),
+                        }
+                    }
+                }
+                if let Err(e) = modem_cli.setup_signal(config.signal_refresh_rate) {
+                    warn!("Can't setup signal refresh: {}", e);
+                }
+            }
+            if modem_events.is_none() {
+                match modem_cli.subscribe() {
+                    Ok(rx) => modem_events = Some(rx),
+                    Err(e) => warn!("Can't subscribe to modem events: {}", e),
+                }
+            }
+            if sms_scan_due && modem_events.is_some() {
+                match modem_cli.list_sms() {
+                    Ok(paths) => {
+                        sms_scan_due = false;
+                        for path in paths {
+                            if !pending_sms.contains(&path) {
+                                pending_sms.push(path);
+                            }
+                        }
+                    }
+                    Err(e) => trace!("Can't list stored SMS: {}", e),
                 }
             }
 
-            if vehicle_gps_enable {
-                trace!("Enable GPS base on user setting");
-                if !modem_cli.is_location_enabled() {
-                    match modem_cli.setup_location(0x07, true) {
-                        Ok(_) => {
-                            trace!("location enable success")
+            if let Err(e) = modem_cli.process_events(MODEM_EVENT_TIMEOUT) {
+                warn!("Can't process modem events: {}", e);
+            }
+            if let Some(rx) = modem_events.as_ref() {
+                for event in rx.try_iter() {
+                    match event {
+                        ModemEvent::StateChanged { old, new, reason } => {
+                            info!("Modem state {} -> {} ({})", old, new, reason);
+                            settings_dirty = true;
                         }
-                        Err(e) => {
-                            info!("Can't perfom action: {:?}", e);
+                        ModemEvent::SignalQuality(quality) => {
+                            info!("SignalQuality: {}% (recent: {})", quality.percent, quality.recent);
+                            match modem_cli.signal_report() {
+                                Ok(report) => trace!("Signal: {:?}", report),
+                                Err(e) => trace!("Can't read signal report: {}", e),
+                            }
+                        }
+                        // Fall back on the serving cell while GNSS has no fix
+                        ModemEvent::Location(location) => match (location.fix(), &location.cell) {
+                            (Some(fix), _) if fix.has_position() => {
+                                trace!("Location: {:?}", fix);
+                                if let Some(utc) = fix.utc() {
+                                    clock.sample(TimeSource::Gnss, utc.into());
+                                }
+                                last_position = Some(fix);
+                            }
+                            (_, Some(cell)) => trace!("Cell location: {:?}", cell),
+                            _ => trace!("No location: {:?}", location),
+                        },
+                        ModemEvent::Registration(state) => {
+                            info!("Registration state: {}", state);
+                            // Home <-> roaming moves go through the roaming policy
+                            settings_dirty = true;
+                            sim_failover.record(state);
+                        }
+                        ModemEvent::ModemRemoved(path) => {
+                            warn!("Modem {} removed", path);
+                            // Forgotten so the setup is redone if it comes back under the same path
+                            if bound_modem.as_deref() == Some(path.as_str()) {
+                                bound_modem = None;
+                                emergency.modem_lost();
+                                data_bearer = None;
+                            }
                         }
+                        ModemEvent::SmsAdded { path, received } => {
+                            if received {
+                                pending_sms.push(path);
+                            }
+                        }
+                        ModemEvent::CallAdded(path) => match modem_cli.call(&path) {
+                            Ok(call) if call.direction == CallDirection::Incoming => {
+                                info!("Incoming call {} from {}", path, call.number);
+                                if emergency.answers_incoming() {
+                                    call_worker.submit(Job::AcceptCall {
+                                        path: path.clone(),
+                                        number: call.number,
+                                    });
+                                }
+                            }
+                            Ok(call) => trace!("Call {} to {} created", path, call.number),
+                            Err(e) => warn!("Can't read call {}: {}", path, e),
+                        },
+                        ModemEvent::CallDeleted(path) => trace!("Call {} deleted", path),
+                        ModemEvent::CallStateChanged { path, old, new, reason } => {
+                            info!("Call {} {} -> {} ({:?})", path, old, new, reason);
+                            let is_emergency = emergency.is_emergency_call(&path);
+                            emergency.call_state(&path, new);
+                            if let Ok(can) = can_conn.as_ref() {
+                                report_call_state(can, &config, new, reason, is_emergency);
+                            }
+                            // ModemManager keeps terminated calls around until deleted
+                            if new == CallState::Terminated {
+                                if let Err(e) = modem_cli.delete_call(&path) {
+                                    trace!("Can't delete call {}: {}", path, e);
+                                }
+                            }
+                        }
+                        ModemEvent::NetworkTime(time) => {
+                            trace!("Network time: {}", time);
+                            if clock.wants(TimeSource::Nitz) {
+                                match modem_cli.network_time_from(&time) {
+                                    Ok(utc) => clock.sample(TimeSource::Nitz, utc.into()),
+                                    Err(e) => warn!("Can't read network time: {}", e),
+                                }
+                            }
+                        }
+                        // The per-modem setup runs once the client binds to it
+                        ModemEvent::ModemAdded(path) => info!("Modem {} appeared", path),
                     }
                 }
-            } else {
-                if modem_cli.is_location_enabled() {
-                    match modem_cli.setup_location(0x03, true) {
-                        Ok(_) => {
-                            trace!("location disabled success")
-                        }
+            }
+
+            if let Some(number) = emergency.poll(&modem_cli) {
+                if !call_worker.submit(Job::Dial(number.clone())) {
+                    emergency.dialed(&number, Err("modem worker stopped".to_owned()));
+                }
+            }
+
+            // Multipart messages are only complete once they leave the Receiving state
+            pending_sms.retain(|path| match modem_cli.sms(path) {
+                Ok(sms) if sms.state == SmsState::Receiving => true,
+                // Stored messages we sent ourselves
+                Ok(sms) if sms.state != SmsState::Received => false,
+                Ok(sms) => {
+                    settings_dirty |= handle_sms(&modem_cli, &worker, sms_channel.as_mut(), &sms, &mut vehicle_gps_enable, &mut vehicle_cell_enable);
+                    false
+                }
+                Err(e) => {
+                    warn!("Can't read SMS {}: {}", path, e);
+                    false
+                }
+            });
+
+            if let Some(query) = config.ussd_balance_query.as_deref() {
+                let interval = Duration::from_secs(config.ussd_balance_interval);
+                let due = config.ussd_balance_interval > 0 && last_balance_query.is_none_or(|last| last.elapsed() >= interval);
+                if due && query_balance(&modem_cli, &worker, query) {
+                    last_balance_query = Some(Instant::now());
+                }
+            }
+
+            for result in worker.results().chain(call_worker.results()) {
+                match &result {
+                    JobResult::InjectAssistance { result: Err(_), .. } => {
+                        agps_retry = Some(Instant::now() + AGPS_RETRY_INTERVAL);
+                    }
+                    JobResult::Dial { number, result } => emergency.dialed(number, result.clone()),
+                    JobResult::Reset {
+                        requested_by,
+                        result: Err(e),
+                    } => {
+                        worker.submit(Job::SendText {
+                            number: requested_by.clone(),
+                            text: format!("{} failed: {}", SmsCommand::RebootModem.as_str(), e),
+                        });
+                    }
+                    _ => {}
+                }
+                log_job_result(result);
+            }
+
+            // A fresh modem has lost what was injected before, the data is queued before
+            // apply_user_settings() starts the GNSS engine
+            if agps_due {
+                agps_due = false;
+                setup_supl(&modem_cli, &config);
+                agps_retry = config.agps_file.as_ref().map(|_| Instant::now());
+            }
+            if let (Some(path), Some(at)) = (config.agps_file.as_deref(), agps_retry) {
+                if at <= Instant::now() {
+                    agps_retry = match inject_agps(&modem_cli, &worker, path, Duration::from_secs(config.agps_validity)) {
+                        Ok(true) => None,
+                        Ok(false) => Some(Instant::now() + AGPS_RETRY_INTERVAL),
                         Err(e) => {
-                            info!("Can't perfom action: {:?}", e);
+                            warn!("Can't inject assistance data {}: {}", path, e);
+                            Some(Instant::now() + AGPS_RETRY_INTERVAL)
                         }
+                    };
+                }
+            }
+
+            // Later updates come with NetworkTime events
+            if network_time_due {
+                network_time_due = false;
+                if let Ok(timezone) = modem_cli.network_timezone() {
+                    info!("Network timezone: {:?}", timezone);
+                }
+                if clock.wants(TimeSource::Nitz) {
+                    match modem_cli.network_time() {
+                        Ok(utc) => clock.sample(TimeSource::Nitz, utc.into()),
+                        Err(e) => trace!("Can't read network time: {}", e),
+                    }
+                }
+            }
+
+            if inventory_due {
+                match publish_inventory(&modem_cli, &config.inventory_path) {
+                    Ok(_) => inventory_due = false,
+                    Err(e) => warn!("Can't publish modem inventory: {}", e),
+                }
+            }
+
+            let interval = Duration::from_secs(config.cell_info_interval);
+            if config.cell_info_interval > 0 && last_cell_info.is_none_or(|last| last.elapsed() >= interval) {
+                // A position is only used once so that stale fixes don't end up on the map
+                log_cell_info(&modem_cli, last_position.take().as_ref());
+                last_cell_info = Some(Instant::now());
+            }
+
+            if last_recovery_check.elapsed() >= RECOVERY_POLL_INTERVAL {
+                last_recovery_check = Instant::now();
+                // Polled as well, a modem stuck searching sends no more change events
+                if let Ok(registration) = modem_cli.registration_state() {
+                    sim_failover.record(registration);
+                }
+                if sim_failover.is_due() {
+                    if let Err(e) = sim_failover.fail_over(&modem_cli) {
+                        warn!("Can't switch SIM slot: {}", e);
+                    }
+                }
+                if let Some(step) = recovery.check(modem_health(&modem_cli)) {
+                    match step.run(&modem_cli) {
+                        Ok(_) => info!("Recovery step {:?} done", step),
+                        Err(e) => warn!("Recovery step {:?} failed: {}", step, e),
                     }
+                    // Every step drops ongoing calls
+                    emergency.modem_lost();
+                    settings_dirty = true;
                 }
             }
 
-            if vehicle_cell_enable {
-                trace!("Enable Data LTE based on usersetting");
+            if settings_dirty || settings_retry.is_some_and(|at| at <= Instant::now()) {
+                settings_dirty = false;
+                settings_retry = match apply_user_settings(&modem_cli, &config, vehicle_gps_enable, vehicle_cell_enable, &mut data_bearer, &mut pin_rejected) {
+                    true => None,
+                    false => Some(Instant::now() + SETTINGS_RETRY_INTERVAL),
+                };
             }
         } else {
             info!("Modem is not ready");
@@ -0,0 +1,146 @@
+use modemcli::modem_cli::{IonModemCli, IonModemCliBuilder};
+use modemcli::modem_error::ModemError;
+use std::sync::mpsc::{self, Receiver, Sender, TryIter};
+use std::thread;
+use std::time::Duration;
+
+const USSD_TIMEOUT: Duration = Duration::from_secs(30);
+// Assistance files are a few tens of kB, the modem takes a while to store them
+const AGPS_INJECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Modem calls that wait on the network or the modem for seconds
+pub enum Job {
+    // USSD code, e.g. the prepaid balance query
+    Ussd(String),
+    // Assistance data read from the file
+    InjectAssistance { path: String, data: Vec<u8> },
+    // Number to call
+    Dial(String),
+    AcceptCall { path: String, number: String },
+    SendText { number: String, text: String },
+    DeleteSms(String),
+    // Reset asked for by an SMS command, requested_by is told when it fails
+    Reset { requested_by: String },
+}
+
+pub enum JobResult {
+    Ussd {
+        code: String,
+        reply: Result<String, String>,
+    },
+    InjectAssistance {
+        path: String,
+        result: Result<usize, String>,
+    },
+    // Object path of the call on success
+    Dial {
+        number: String,
+        result: Result<String, String>,
+    },
+    AcceptCall {
+        number: String,
+        result: Result<(), String>,
+    },
+    SendText {
+        number: String,
+        result: Result<(), String>,
+    },
+    DeleteSms {
+        path: String,
+        result: Result<(), String>,
+    },
+    Reset {
+        requested_by: String,
+        result: Result<(), String>,
+    },
+}
+
+// Runs the slow jobs on a thread with its own D-Bus connection so that the main loop
+// keeps serving CAN and modem events meanwhile. Jobs run one after the other.
+pub struct ModemWorker {
+    jobs: Sender<Job>,
+    results: Receiver<JobResult>,
+}
+
+impl ModemWorker {
+    // The worker's client is built from the main client's settings (bus, selector, AT policy...)
+    pub fn spawn(name: &str, builder: IonModemCliBuilder) -> std::io::Result<Self> {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let (result_tx, results) = mpsc::channel();
+        thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || {
+                // IonModemCli holds its connection in an Rc, it's created on this thread
+                let mut modem_cli = builder.build();
+                for job in job_rx {
+                    if result_tx.send(run(&mut modem_cli, job)).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(ModemWorker { jobs, results })
+    }
+
+    // False when the worker thread is gone
+    pub fn submit(&self, job: Job) -> bool {
+        self.jobs.send(job).is_ok()
+    }
+
+    pub fn results(&self) -> TryIter<'_, JobResult> {
+        self.results.try_iter()
+    }
+}
+
+// Jobs fail without a modem call while the worker isn't bound to a modem
+fn when_ready<T>(ready: bool, call: impl FnOnce() -> Result<T, ModemError>) -> Result<T, String> {
+    match ready {
+        true => call().map_err(|e| e.to_string()),
+        false => Err("modem not ready".to_owned()),
+    }
+}
+
+fn run(modem_cli: &mut IonModemCli, job: Job) -> JobResult {
+    let ready = modem_cli.waiting_for_ready();
+    let modem_cli = &*modem_cli;
+    match job {
+        Job::Ussd(code) => {
+            let reply = when_ready(ready, || {
+                modem_cli
+                    .ussd_initiate(&code, USSD_TIMEOUT)
+                    .map(|session| session.reply().to_owned())
+            });
+            JobResult::Ussd { code, reply }
+        }
+        Job::InjectAssistance { path, data } => {
+            let result = when_ready(ready, || {
+                modem_cli
+                    .inject_assistance_data(&data, AGPS_INJECT_TIMEOUT)
+                    .map(|_| data.len())
+            });
+            JobResult::InjectAssistance { path, result }
+        }
+        Job::Dial(number) => {
+            let result = when_ready(ready, || modem_cli.dial(&number));
+            JobResult::Dial { number, result }
+        }
+        Job::AcceptCall { path, number } => {
+            let result = when_ready(ready, || modem_cli.accept_call(&path));
+            JobResult::AcceptCall { number, result }
+        }
+        Job::SendText { number, text } => {
+            let result = when_ready(ready, || modem_cli.send_text(&number, &text).map(|_| ()));
+            JobResult::SendText { number, result }
+        }
+        Job::DeleteSms(path) => {
+            let result = when_ready(ready, || modem_cli.delete_sms(&path));
+            JobResult::DeleteSms { path, result }
+        }
+        Job::Reset { requested_by } => {
+            let result = when_ready(ready, || modem_cli.reset());
+            JobResult::Reset {
+                requested_by,
+                result,
+            }
+        }
+    }
+}
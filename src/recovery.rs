@@ -0,0 +1,181 @@
+use log::{info, warn};
+use modemcli::modem_cli::IonModemCli;
+use modemcli::modem_error::ModemError;
+use modemcli::modem_state::PowerState;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecoveryStep {
+    // Disable then enable the modem
+    Reenable,
+    // Cycle the radio through low power
+    LowPower,
+    // Reboot the modem
+    Reset,
+}
+
+impl RecoveryStep {
+    fn next(self) -> Self {
+        match self {
+            RecoveryStep::Reenable => RecoveryStep::LowPower,
+            RecoveryStep::LowPower | RecoveryStep::Reset => RecoveryStep::Reset,
+        }
+    }
+
+    pub fn run(self, modem_cli: &IonModemCli) -> Result<(), ModemError> {
+        match self {
+            RecoveryStep::Reenable => {
+                modem_cli.setup_modem_enable(false)?;
+                modem_cli.setup_modem_enable(true)
+            }
+            RecoveryStep::LowPower => {
+                // SetPowerState is refused while the modem is enabled
+                modem_cli.setup_modem_enable(false)?;
+                modem_cli.set_power_state(PowerState::Low)?;
+                modem_cli.set_power_state(PowerState::On)?;
+                modem_cli.setup_modem_enable(true)
+            }
+            RecoveryStep::Reset => modem_cli.reset(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    Healthy,
+    // FAILED or UNKNOWN state
+    Failed,
+    // Enabled but not registering
+    RegistrationStalled,
+}
+
+// Escalates Reenable -> LowPower -> Reset while the modem stays unhealthy. Each step
+// is taken once the modem has been unhealthy for the matching threshold since the
+// previous step, a zero threshold disables recovery for that condition.
+pub struct RecoveryLadder {
+    failed_timeout: Duration,
+    registration_timeout: Duration,
+    // Start of the unhealthy period, or time of the last step taken
+    since: Option<Instant>,
+    next_step: RecoveryStep,
+}
+
+impl RecoveryLadder {
+    pub fn new(failed_timeout: Duration, registration_timeout: Duration) -> Self {
+        RecoveryLadder {
+            failed_timeout,
+            registration_timeout,
+            since: None,
+            next_step: RecoveryStep::Reenable,
+        }
+    }
+
+    // Feed the current health, returns the step to run when one is due
+    pub fn check(&mut self, health: Health) -> Option<RecoveryStep> {
+        let timeout = match health {
+            Health::Healthy => {
+                if self.since.take().is_some() && self.next_step != RecoveryStep::Reenable {
+                    info!("Modem recovered");
+                }
+                self.next_step = RecoveryStep::Reenable;
+                return None;
+            }
+            Health::Failed => self.failed_timeout,
+            Health::RegistrationStalled => self.registration_timeout,
+        };
+        if timeout.is_zero() {
+            return None;
+        }
+
+        let since = *self.since.get_or_insert_with(Instant::now);
+        if since.elapsed() < timeout {
+            return None;
+        }
+        let step = self.next_step;
+        warn!(
+            "Modem {:?} for {:?}, recovery step {:?}",
+            health,
+            since.elapsed(),
+            step
+        );
+        self.next_step = step.next();
+        self.since = Some(Instant::now());
+        Some(step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+
+    // Unhealthy for longer than TIMEOUT, then checked
+    fn check_after_timeout(ladder: &mut RecoveryLadder, health: Health) -> Option<RecoveryStep> {
+        thread::sleep(TIMEOUT + Duration::from_millis(5));
+        ladder.check(health)
+    }
+
+    #[test]
+    fn healthy_modem_is_left_alone() {
+        let mut ladder = RecoveryLadder::new(TIMEOUT, TIMEOUT);
+        assert_eq!(ladder.check(Health::Healthy), None);
+        assert_eq!(check_after_timeout(&mut ladder, Health::Healthy), None);
+    }
+
+    #[test]
+    fn escalates_while_unhealthy() {
+        let mut ladder = RecoveryLadder::new(TIMEOUT, TIMEOUT);
+        assert_eq!(ladder.check(Health::Failed), None);
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::Failed),
+            Some(RecoveryStep::Reenable)
+        );
+        // The next step waits a whole timeout again
+        assert_eq!(ladder.check(Health::Failed), None);
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::RegistrationStalled),
+            Some(RecoveryStep::LowPower)
+        );
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::Failed),
+            Some(RecoveryStep::Reset)
+        );
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::Failed),
+            Some(RecoveryStep::Reset)
+        );
+    }
+
+    #[test]
+    fn recovery_starts_over() {
+        let mut ladder = RecoveryLadder::new(TIMEOUT, TIMEOUT);
+        ladder.check(Health::Failed);
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::Failed),
+            Some(RecoveryStep::Reenable)
+        );
+        assert_eq!(ladder.check(Health::Healthy), None);
+        ladder.check(Health::Failed);
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::Failed),
+            Some(RecoveryStep::Reenable)
+        );
+    }
+
+    #[test]
+    fn zero_timeout_disables_recovery() {
+        let mut ladder = RecoveryLadder::new(TIMEOUT, Duration::ZERO);
+        ladder.check(Health::RegistrationStalled);
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::RegistrationStalled),
+            None
+        );
+        ladder.check(Health::Failed);
+        assert_eq!(
+            check_after_timeout(&mut ladder, Health::Failed),
+            Some(RecoveryStep::Reenable)
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use log::{info, warn};
+use modemcli::modem_3gpp::RegistrationState;
+use modemcli::modem_cli::IonModemCli;
+use modemcli::modem_error::ModemError;
+use std::time::{Duration, Instant};
+
+// Switch to the next populated SIM slot once the modem stayed unregistered (denied, searching,
+// idle...) for `timeout`. A zero timeout disables the failover.
+#[derive(Clone, Debug)]
+pub struct SimFailover {
+    timeout: Duration,
+    unregistered_since: Option<Instant>,
+}
+
+impl SimFailover {
+    pub fn new(timeout: Duration) -> Self {
+        SimFailover {
+            timeout,
+            unregistered_since: None,
+        }
+    }
+
+    // Feed every registration state, from change events and from polling
+    pub fn record(&mut self, registration: RegistrationState) {
+        if registration.is_registered() {
+            self.unregistered_since = None;
+        } else if self.unregistered_since.is_none() {
+            self.unregistered_since = Some(Instant::now());
+        }
+    }
+
+    // Start over, e.g. for a modem that just appeared
+    pub fn reset(&mut self) {
+        self.unregistered_since = None;
+    }
+
+    // Whether it's time to switch slots
+    pub fn is_due(&self) -> bool {
+        !self.timeout.is_zero()
+            && self
+                .unregistered_since
+                .is_some_and(|since| since.elapsed() >= self.timeout)
+    }
+
+    // Make the next slot holding a SIM the primary one
+    pub fn fail_over(&mut self, modem_cli: &IonModemCli) -> Result<(), ModemError> {
+        // The next SIM gets the whole timeout to register
+        self.unregistered_since = None;
+        let slots = modem_cli.sim_slots()?;
+        let primary = slots.iter().position(|slot| slot.primary).unwrap_or(0);
+        let next = slots
+            .iter()
+            .cycle()
+            .skip(primary + 1)
+            .take(slots.len().saturating_sub(1))
+            .find(|slot| slot.sim.is_some());
+
+        match next {
+            Some(slot) => {
+                warn!(
+                    "Not registered for {}s, switching to SIM slot {}",
+                    self.timeout.as_secs(),
+                    slot.slot
+                );
+                modem_cli.set_primary_sim_slot(slot.slot)
+            }
+            None => {
+                info!(
+                    "Not registered for {}s, no other SIM slot to switch to",
+                    self.timeout.as_secs()
+                );
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// log4rs logger every remote command goes through, kept at info level by the logging crate
+pub const AUDIT_TARGET: &str = "sms_command";
+
+// Hex characters of the HMAC carried by a command SMS
+const MAC_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmsCommand {
+    GpsOn,
+    GpsOff,
+    DataOn,
+    DataOff,
+    RebootModem,
+    Status,
+}
+
+impl SmsCommand {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SmsCommand::GpsOn => "GPS ON",
+            SmsCommand::GpsOff => "GPS OFF",
+            SmsCommand::DataOn => "DATA ON",
+            SmsCommand::DataOff => "DATA OFF",
+            SmsCommand::RebootModem => "REBOOT MODEM",
+            SmsCommand::Status => "STATUS",
+        }
+    }
+}
+
+impl FromStr for SmsCommand {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let words: Vec<String> = value
+            .split_whitespace()
+            .map(|word| word.to_ascii_uppercase())
+            .collect();
+        match words.join(" ").as_str() {
+            "GPS ON" => Ok(SmsCommand::GpsOn),
+            "GPS OFF" => Ok(SmsCommand::GpsOff),
+            "DATA ON" => Ok(SmsCommand::DataOn),
+            "DATA OFF" => Ok(SmsCommand::DataOff),
+            "REBOOT MODEM" => Ok(SmsCommand::RebootModem),
+            "STATUS" => Ok(SmsCommand::Status),
+            _ => Err(format!("unknown command '{}'", value.trim())),
+        }
+    }
+}
+
+// Command SMS are "<COMMAND> <nonce> <mac>", e.g. "GPS ON 1718000000 3f2a9c0d11e4b7a8", where
+// mac is the first 16 hex characters of HMAC-SHA256(secret, "<nonce> <COMMAND>") and nonce is
+// a number that must grow from one command to the next for a given sender (e.g. a unix time).
+pub struct SmsCommandChannel {
+    whitelist: Vec<String>,
+    secret: Vec<u8>,
+    // Highest nonce accepted per sender, replayed messages are rejected. Kept in nonce_path
+    // so that a restart doesn't make captured commands valid again.
+    last_nonce: HashMap<String, u64>,
+    nonce_path: PathBuf,
+}
+
+// "+33 6 12 34 56 78" and "+33612345678" are the same sender
+fn normalize_number(number: &str) -> String {
+    number.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+// "<sender> <nonce>" lines, a missing file means no command was accepted yet
+fn load_nonces(path: &Path) -> HashMap<String, u64> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            warn!(target: AUDIT_TARGET, "Can't read SMS nonces {}: {}", path.display(), e);
+            return HashMap::new();
+        }
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (sender, nonce) = line.split_once(' ')?;
+            Some((sender.to_owned(), nonce.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// Written aside then renamed, a power cut never leaves a truncated file
+fn save_nonces(path: &Path, nonces: &HashMap<String, u64>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text: String = nonces
+        .iter()
+        .map(|(sender, nonce)| format!("{} {}\n", sender, nonce))
+        .collect();
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, path)
+}
+
+impl SmsCommandChannel {
+    // None when no sender or no secret is configured, the channel is then disabled
+    pub fn new(whitelist: &[String], secret: &str, nonce_path: &str) -> Option<Self> {
+        if whitelist.is_empty() || secret.is_empty() {
+            return None;
+        }
+        let nonce_path = PathBuf::from(nonce_path);
+        Some(SmsCommandChannel {
+            whitelist: whitelist
+                .iter()
+                .map(|number| normalize_number(number))
+                .collect(),
+            secret: secret.as_bytes().to_vec(),
+            last_nonce: load_nonces(&nonce_path),
+            nonce_path,
+        })
+    }
+
+    fn mac(&self, nonce: &str, command: SmsCommand) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(format!("{} {}", nonce, command.as_str()).as_bytes());
+        mac
+    }
+
+    // Check sender, token and nonce, every outcome is written to the audit log
+    pub fn authenticate(&mut self, sender: &str, text: &str) -> Result<SmsCommand, String> {
+        let result = self.check(sender, text);
+        match &result {
+            Ok(command) => {
+                info!(target: AUDIT_TARGET, "Accepted '{}' from {}", command.as_str(), sender)
+            }
+            Err(reason) => warn!(target: AUDIT_TARGET, "Rejected SMS from {}: {}", sender, reason),
+        }
+        result
+    }
+
+    fn check(&mut self, sender: &str, text: &str) -> Result<SmsCommand, String> {
+        let sender = normalize_number(sender);
+        if !self.whitelist.contains(&sender) {
+            return Err("sender not allowed".to_owned());
+        }
+
+        let mut words: Vec<&str> = text.split_whitespace().collect();
+        let (mac, nonce) = match (words.pop(), words.pop()) {
+            (Some(mac), Some(nonce)) => (mac, nonce),
+            _ => return Err("missing token".to_owned()),
+        };
+        let command: SmsCommand = words.join(" ").parse()?;
+
+        let expected = match hex_decode(mac) {
+            Some(expected) if mac.len() == MAC_LEN => expected,
+            _ => return Err("malformed token".to_owned()),
+        };
+        self.mac(nonce, command)
+            .verify_truncated_left(&expected)
+            .map_err(|_| "bad token".to_owned())?;
+
+        let nonce: u64 = nonce.parse().map_err(|_| "malformed nonce".to_owned())?;
+        if self
+            .last_nonce
+            .get(&sender)
+            .is_some_and(|last| nonce <= *last)
+        {
+            return Err("replayed command".to_owned());
+        }
+        // A nonce that can't be recorded could be replayed after a restart
+        let mut nonces = self.last_nonce.clone();
+        nonces.insert(sender, nonce);
+        save_nonces(&self.nonce_path, &nonces).map_err(|e| format!("can't record nonce: {}", e))?;
+        self.last_nonce = nonces;
+        Ok(command)
+    }
+}
+
+fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: &str = "+33612345678";
+
+    fn nonce_path(test: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("modemhandler-{}-{}", std::process::id(), test));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn channel(path: &Path) -> SmsCommandChannel {
+        SmsCommandChannel::new(&[SENDER.to_owned()], "s3cret", path.to_str().unwrap()).unwrap()
+    }
+
+    fn sms(channel: &SmsCommandChannel, command: SmsCommand, nonce: u64) -> String {
+        let mac = channel
+            .mac(&nonce.to_string(), command)
+            .finalize()
+            .into_bytes();
+        let token: String = mac
+            .iter()
+            .take(MAC_LEN / 2)
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("{} {} {}", command.as_str(), nonce, token)
+    }
+
+    #[test]
+    fn disabled_without_secret_or_sender() {
+        assert!(SmsCommandChannel::new(&[SENDER.to_owned()], "", "/nonexistent").is_none());
+        assert!(SmsCommandChannel::new(&[], "s3cret", "/nonexistent").is_none());
+    }
+
+    #[test]
+    fn accepts_signed_command() {
+        let path = nonce_path("accepts");
+        let mut channel = channel(&path);
+        let text = sms(&channel, SmsCommand::GpsOff, 1718000000).to_lowercase();
+        assert_eq!(
+            channel.check("+33 6 12 34 56 78", &text),
+            Ok(SmsCommand::GpsOff)
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn rejects_bad_tokens() {
+        let path = nonce_path("tokens");
+        let mut channel = channel(&path);
+        let text = sms(&channel, SmsCommand::DataOn, 1);
+        assert_eq!(
+            channel.check("+33700000000", &text),
+            Err("sender not allowed".to_owned())
+        );
+        assert_eq!(
+            channel.check(SENDER, "STATUS"),
+            Err("missing token".to_owned())
+        );
+        assert_eq!(
+            channel.check(SENDER, "DATA ON 1 3f2a"),
+            Err("malformed token".to_owned())
+        );
+        assert_eq!(
+            channel.check(SENDER, "DATA ON 1 3f2a9c0d11e4b7a8"),
+            Err("bad token".to_owned())
+        );
+        // The MAC covers the command
+        let tampered = text.replace("DATA ON", "DATA OFF");
+        assert_eq!(
+            channel.check(SENDER, &tampered),
+            Err("bad token".to_owned())
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_replayed_nonce() {
+        let path = nonce_path("replay");
+        let mut channel = channel(&path);
+        let first = sms(&channel, SmsCommand::Status, 100);
+        assert_eq!(channel.check(SENDER, &first), Ok(SmsCommand::Status));
+        assert_eq!(
+            channel.check(SENDER, &first),
+            Err("replayed command".to_owned())
+        );
+        let older = sms(&channel, SmsCommand::Status, 99);
+        assert_eq!(
+            channel.check(SENDER, &older),
+            Err("replayed command".to_owned())
+        );
+        let newer = sms(&channel, SmsCommand::Status, 101);
+        assert_eq!(channel.check(SENDER, &newer), Ok(SmsCommand::Status));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn nonces_survive_a_restart() {
+        let path = nonce_path("restart");
+        let mut before = channel(&path);
+        let text = sms(&before, SmsCommand::RebootModem, 42);
+        assert_eq!(before.check(SENDER, &text), Ok(SmsCommand::RebootModem));
+        let mut restarted = channel(&path);
+        assert_eq!(
+            restarted.check(SENDER, &text),
+            Err("replayed command".to_owned())
+        );
+        let _ = fs::remove_file(path);
+    }
+}